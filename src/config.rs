@@ -1,20 +1,35 @@
+use crate::front_matter;
+use crate::json;
 use anyhow::{Context, Result};
 use serde_derive::Deserialize;
 use std::path::Path;
 
 static GALLERY_FOLDER_NAME: &str = "images";
 static OUTPUT_FOLDER_NAME: &str = "_output";
+pub const DEFAULT_PAGINATE_BY: usize = 10;
+pub const DEFAULT_FEED_LIMIT: usize = 20;
 
 #[derive(Debug, Deserialize, Default)]
 pub struct Config {
     pub title: Option<String>,
+    pub base_url: Option<String>,
+    pub locale: Option<String>,
     pub template_dir: Option<String>,
     pub output_dir: Option<String>,
     pub logging: Option<LogKind>,
     pub remove_numbered_prefix: Option<bool>,
     pub copy_dirs: Option<Vec<String>>,
     pub gallery: Option<Gallery>,
+    pub sections: Option<Vec<Section>>,
+    pub feed: Option<Feed>,
+    /// Writes a json file next to the html of every page, an `index.json` of
+    /// the whole site and a JSON Feed.
+    pub json_export: Option<bool>,
+    pub sitemap: Option<Sitemap>,
+    pub markdown: Option<Markdown>,
     pub sync_settings: Option<SyncSettings>,
+    /// Free-form settings of the `[extra]` table for use in templates.
+    pub extra: Option<toml::Table>,
 }
 
 #[derive(Debug, Clone, Copy, Deserialize)]
@@ -44,6 +59,58 @@ impl From<ImageFormat> for image::ImageFormat {
     }
 }
 
+/// A directory whose pages are dated posts. It gets listing pages sorted by
+/// date, `paginate_by` posts per page.
+#[derive(Debug, Deserialize)]
+pub struct Section {
+    pub dir: String,
+    pub paginate_by: Option<usize>,
+    pub layout: Option<String>,
+}
+
+/// Atom and RSS feeds of the newest dated pages. Without `sections` there is
+/// one feed for the whole site, otherwise one feed in each of the directories.
+#[derive(Debug, Default, Deserialize)]
+pub struct Feed {
+    pub formats: Option<Vec<FeedFormat>>,
+    pub sections: Option<Vec<String>>,
+    pub limit: Option<usize>,
+    pub full_content: Option<bool>,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Deserialize)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+    Json,
+}
+
+impl FeedFormat {
+    pub const fn file_name(self) -> &'static str {
+        match self {
+            Self::Atom => "atom.xml",
+            Self::Rss => "rss.xml",
+            Self::Json => "feed.json",
+        }
+    }
+}
+
+impl Feed {
+    pub fn formats(&self) -> Vec<FeedFormat> {
+        self.formats
+            .clone()
+            .unwrap_or_else(|| vec![FeedFormat::Atom, FeedFormat::Rss])
+    }
+}
+
+/// A `sitemap.xml` of all rendered pages and a `robots.txt` pointing at it.
+/// `disallow` lists the paths crawlers should skip.
+#[derive(Debug, Deserialize)]
+pub struct Sitemap {
+    pub robots_txt: Option<bool>,
+    pub disallow: Option<Vec<String>>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Gallery {
     pub img_dir: Option<String>,
@@ -54,6 +121,93 @@ pub struct Gallery {
     pub thumb_height: u32,
 }
 
+/// Switches for the `CommonMark` extensions of pulldown-cmark. Tables, footnotes,
+/// strikethrough and task lists are enabled unless switched off explicitly.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Markdown {
+    pub tables: Option<bool>,
+    pub footnotes: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub tasklists: Option<bool>,
+    pub smart_punctuation: Option<bool>,
+    pub heading_attributes: Option<bool>,
+    pub definition_list: Option<bool>,
+    pub math: Option<bool>,
+    pub highlight_theme: Option<String>,
+    pub highlight_style: Option<HighlightStyle>,
+    pub broken_links: Option<LinkCheck>,
+}
+
+/// How highlighted code blocks are colored: via inline `style` attributes or
+/// via CSS classes backed by the generated `syntax.css` stylesheet.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Deserialize)]
+pub enum HighlightStyle {
+    Inline,
+    Classes,
+}
+
+impl Markdown {
+    fn extensions(&self) -> [(&'static str, bool, pulldown_cmark::Options); 8] {
+        use pulldown_cmark::Options;
+        [
+            (
+                "tables",
+                self.tables.unwrap_or(true),
+                Options::ENABLE_TABLES,
+            ),
+            (
+                "footnotes",
+                self.footnotes.unwrap_or(true),
+                Options::ENABLE_FOOTNOTES,
+            ),
+            (
+                "strikethrough",
+                self.strikethrough.unwrap_or(true),
+                Options::ENABLE_STRIKETHROUGH,
+            ),
+            (
+                "tasklists",
+                self.tasklists.unwrap_or(true),
+                Options::ENABLE_TASKLISTS,
+            ),
+            (
+                "smart_punctuation",
+                self.smart_punctuation.unwrap_or(false),
+                Options::ENABLE_SMART_PUNCTUATION,
+            ),
+            (
+                "heading_attributes",
+                self.heading_attributes.unwrap_or(false),
+                Options::ENABLE_HEADING_ATTRIBUTES,
+            ),
+            (
+                "definition_list",
+                self.definition_list.unwrap_or(false),
+                Options::ENABLE_DEFINITION_LIST,
+            ),
+            ("math", self.math.unwrap_or(false), Options::ENABLE_MATH),
+        ]
+    }
+
+    pub fn options(&self) -> pulldown_cmark::Options {
+        self.extensions()
+            .into_iter()
+            .filter(|(_, enabled, _)| *enabled)
+            .fold(
+                pulldown_cmark::Options::empty(),
+                |options, (_, _, option)| options | option,
+            )
+    }
+}
+
+/// What to do about links between pages whose target page or anchor is missing.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Deserialize)]
+pub enum LinkCheck {
+    Ignore,
+    Warn,
+    Error,
+}
+
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Deserialize)]
 pub enum FtpProtocol {
     Ftp,
@@ -115,6 +269,9 @@ impl Config {
                 gallery.img_format = Some(ImageFormat::Jpg);
             }
         }
+        if self.markdown.is_none() {
+            self.markdown = Some(Markdown::default());
+        }
         if self.template_dir.is_some() {
             let template_path = base_path.join(self.template_dir.as_ref().unwrap().as_str());
             assert!(
@@ -161,11 +318,27 @@ impl Config {
         }
     }
 
+    /// The `config` object of the templates.
+    pub fn to_liquid(&self) -> liquid::model::Value {
+        let extra = self.extra.clone().unwrap_or_default();
+        liquid::model::Value::Object(liquid::object!({
+            "title": self.title.clone(),
+            "base_url": self.base_url.clone(),
+            "locale": self.locale.clone().unwrap_or_else(|| "en".to_string()),
+            "extra": front_matter::toml_to_liquid(toml::Value::Table(extra)),
+        }))
+    }
+
     pub fn print(&self) {
         use term_painter::Attr::Bold;
         use term_painter::ToStyle;
         let none_string = "None".to_string();
         println!("Title : {}", self.title.as_ref().unwrap_or(&none_string));
+        println!(
+            "Base url : {}",
+            self.base_url.as_ref().unwrap_or(&none_string)
+        );
+        println!("Locale : {}", self.locale.as_deref().unwrap_or("en"));
         println!(
             "Template directory : {}",
             self.template_dir.as_ref().unwrap_or(&none_string)
@@ -176,9 +349,24 @@ impl Config {
                 .as_ref()
                 .map_or(OUTPUT_FOLDER_NAME, String::as_str)
         );
+        if let Some(markdown) = &self.markdown {
+            println!("{}", Bold.paint("Markdown"));
+            for (name, enabled, _) in markdown.extensions() {
+                println!("  {name}: {enabled}");
+            }
+            println!(
+                "  broken_links: {:?}",
+                markdown.broken_links.unwrap_or(LinkCheck::Warn)
+            );
+            if let Some(theme) = &markdown.highlight_theme {
+                println!(
+                    "  highlight: {theme} ({:?})",
+                    markdown.highlight_style.unwrap_or(HighlightStyle::Inline)
+                );
+            }
+        }
         println!("{}", Bold.paint("SyncSettings"));
-        if self.sync_settings.is_some() {
-            let sync_settings = self.sync_settings.as_ref().unwrap();
+        if let Some(sync_settings) = &self.sync_settings {
             println!("  FTP server: {}", sync_settings.ftp_server);
             println!("  FTP port: {}", sync_settings.ftp_port.unwrap_or(21));
             println!("  FTP user: {}", sync_settings.ftp_user);
@@ -187,8 +375,7 @@ impl Config {
                 sync_settings.ftp_overwrite.unwrap_or(false)
             );
         }
-        if self.gallery.is_some() {
-            let gallery = self.gallery.as_ref().unwrap();
+        if let Some(gallery) = &self.gallery {
             println!("{}", Bold.paint("Gallery"));
             println!(
                 "  image directory: {}",
@@ -206,5 +393,65 @@ impl Config {
                 gallery.thumb_width, gallery.thumb_height
             );
         }
+        for section in self.sections.iter().flatten() {
+            println!("{}", Bold.paint("Section"));
+            println!("  directory: {}", section.dir);
+            println!(
+                "  paginate by: {}",
+                section.paginate_by.unwrap_or(DEFAULT_PAGINATE_BY)
+            );
+            println!(
+                "  layout: {}",
+                section.layout.as_deref().unwrap_or("section")
+            );
+        }
+        self.print_generated_files();
+        if let Some(extra) = self.extra.as_ref().filter(|extra| !extra.is_empty()) {
+            println!("{}", Bold.paint("Extra"));
+            for (key, value) in extra {
+                println!("  {key}: {value}");
+            }
+        }
+    }
+
+    /// The formats of the feeds of the `[feed]` table. A JSON Feed is written
+    /// whenever `json_export` is on, also without that table.
+    pub fn feed_formats(&self) -> Vec<FeedFormat> {
+        let mut formats = self.feed.as_ref().map(Feed::formats).unwrap_or_default();
+        if self.json_export.unwrap_or(false) && !formats.contains(&FeedFormat::Json) {
+            formats.push(FeedFormat::Json);
+        }
+        formats
+    }
+
+    /// Prints the settings of the feeds, the sitemap and the json export.
+    fn print_generated_files(&self) {
+        use term_painter::Attr::Bold;
+        use term_painter::ToStyle;
+        if let Some(feed) = &self.feed {
+            println!("{}", Bold.paint("Feed"));
+            println!("  formats: {:?}", self.feed_formats());
+            println!(
+                "  sections: {}",
+                feed.sections
+                    .as_ref()
+                    .map_or_else(|| "whole site".to_string(), |dirs| dirs.join(", "))
+            );
+            println!("  limit: {}", feed.limit.unwrap_or(DEFAULT_FEED_LIMIT));
+            println!("  full content: {}", feed.full_content.unwrap_or(false));
+        }
+        if let Some(sitemap) = &self.sitemap {
+            println!("{}", Bold.paint("Sitemap"));
+            println!("  robots.txt: {}", sitemap.robots_txt.unwrap_or(true));
+            for path in sitemap.disallow.iter().flatten() {
+                println!("  disallow: {path}");
+            }
+        }
+        if self.json_export.unwrap_or(false) {
+            println!("{}", Bold.paint("Json export"));
+            println!("  site index: {}", json::INDEX_JSON);
+            println!("  home page: {}", json::HOME_JSON);
+            println!("  feed: {}", FeedFormat::Json.file_name());
+        }
     }
 }
@@ -0,0 +1,123 @@
+use crate::front_matter;
+use anyhow::{Context, Result};
+use liquid::model::{KString, Object, Value};
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+static DATA_DIR: &str = "_data";
+
+/// Loads the TOML, JSON, YAML and CSV files of the `_data` directory of the
+/// project. Every file becomes an entry named after its file stem, sub
+/// directories become nested objects.
+pub fn load(path: &Path) -> Result<Value> {
+    let data_dir = path.join(DATA_DIR);
+    if !data_dir.is_dir() {
+        return Ok(Value::Object(Object::new()));
+    }
+    load_dir(&data_dir).map(Value::Object)
+}
+
+/// Tells whether anything in the `_data` directory was changed, added or
+/// removed after `since`.
+pub fn is_modified(path: &Path, since: SystemTime) -> bool {
+    WalkDir::new(path.join(DATA_DIR))
+        .into_iter()
+        .filter_map(Result::ok)
+        .any(|entry| {
+            entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .is_some_and(|modified| modified > since)
+        })
+}
+
+fn load_dir(dir: &Path) -> Result<Object> {
+    let mut data = Object::new();
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("failed to read data directory {}", dir.display()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+    for entry in entries {
+        let path = entry.path();
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        let value = if path.is_dir() {
+            Value::Object(load_dir(&path)?)
+        } else if let Some(value) = load_file(&path)
+            .with_context(|| format!("failed to load data file {}", path.display()))?
+        {
+            value
+        } else {
+            log::warn!("Ignoring unsupported data file {}", path.display());
+            continue;
+        };
+        data.insert(KString::from_ref(name), value);
+    }
+    Ok(data)
+}
+
+fn load_file(path: &Path) -> Result<Option<Value>> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    let value = match extension.as_deref() {
+        Some("toml") => {
+            let table: toml::Table = toml::from_str(&fs::read_to_string(path)?)?;
+            front_matter::toml_to_liquid(toml::Value::Table(table))
+        }
+        Some("json") => serde_json::from_str(&fs::read_to_string(path)?)?,
+        Some("yaml" | "yml") => serde_yaml::from_str(&fs::read_to_string(path)?)?,
+        Some("csv") => load_csv(path)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+/// Every row of a CSV file becomes an object keyed by the column headers.
+fn load_csv(path: &Path) -> Result<Value> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let row = headers
+            .iter()
+            .zip(record?.iter())
+            .map(|(header, field)| (KString::from_ref(header), Value::scalar(field.to_owned())))
+            .collect::<Object>();
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liquid::ValueView;
+
+    #[test]
+    fn modified_data() {
+        let path = Path::new("examples/simple_blog");
+        assert!(is_modified(path, SystemTime::UNIX_EPOCH));
+        assert!(!is_modified(path, SystemTime::now()));
+    }
+
+    #[test]
+    fn load_data_files() {
+        let data = load(Path::new("examples/simple_blog")).unwrap();
+        let data = data.as_object().unwrap();
+        let board = data.get("board").and_then(ValueView::as_array).unwrap();
+        assert_eq!(board.size(), 3);
+        let chair = board.first().and_then(ValueView::as_object).unwrap();
+        assert_eq!(chair.get("role").unwrap().to_kstr(), "Chair");
+        let training = data.get("training").and_then(ValueView::as_object).unwrap();
+        assert_eq!(training.get("location").unwrap().to_kstr(), "Dog park");
+    }
+}
@@ -0,0 +1,332 @@
+use crate::config::FeedFormat;
+use crate::liquid_filters;
+use crate::site::{self, SitePage};
+use serde_json::json;
+use std::fmt::Write;
+use std::path::Path;
+use time::OffsetDateTime;
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+
+/// Number of words of the summary of a page without a `summary` or
+/// `description` in its front matter.
+const SUMMARY_WORDS: usize = 50;
+
+/// A feed of the site or of one of its directories.
+pub struct Feed<'a> {
+    pub title: String,
+    /// Path of the directory of the feed relative to the site root, empty
+    /// for the feed of the whole site or ending with a `/`.
+    pub dir: String,
+    pub entries: Vec<Entry<'a>>,
+}
+
+pub struct Entry<'a> {
+    pub page: &'a SitePage,
+    /// The rendered markdown of the page without the page template.
+    pub html: String,
+}
+
+/// The `limit` newest dated pages of the site, only those beneath `dir` if
+/// given.
+pub fn newest_pages<'a>(
+    pages: &'a [SitePage],
+    dir: Option<&Path>,
+    limit: usize,
+) -> Vec<&'a SitePage> {
+    let mut pages: Vec<_> = pages
+        .iter()
+        .filter(|page| page.date.is_some())
+        .filter(|page| dir.is_none_or(|dir| page.source.starts_with(dir)))
+        .collect();
+    site::sort_newest_first(&mut pages);
+    pages.truncate(limit);
+    pages
+}
+
+/// The absolute url of a path relative to the site root with characters that
+/// are not allowed in urls, like the spaces of folder names, percent encoded.
+pub fn permalink(base_url: &str, url: &str) -> String {
+    let encoded = url.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+        encoded
+    });
+    liquid_filters::absolute_url(base_url, &encoded)
+}
+
+impl Feed<'_> {
+    /// Path of the feed file relative to the output directory.
+    pub fn url(&self, format: FeedFormat) -> String {
+        format!("{}{}", self.dir, format.file_name())
+    }
+
+    pub fn render(&self, format: FeedFormat, base_url: &str, full_content: bool) -> String {
+        match format {
+            FeedFormat::Atom => self.atom(base_url, full_content),
+            FeedFormat::Rss => self.rss(base_url, full_content),
+            FeedFormat::Json => self.json(base_url, full_content),
+        }
+    }
+
+    /// The newest date of the entries, none for an empty feed. It only
+    /// depends on the pages, so rebuilding the site keeps the feed as it is.
+    fn updated(&self) -> Option<OffsetDateTime> {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.page.updated.max(entry.page.date))
+            .max()
+    }
+
+    fn atom(&self, base_url: &str, full_content: bool) -> String {
+        let link = escape(&permalink(base_url, &self.dir));
+        let feed_url = escape(&permalink(base_url, &self.url(FeedFormat::Atom)));
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        let _ = writeln!(xml, "  <title>{}</title>", escape(&self.title));
+        let _ = writeln!(xml, "  <link href=\"{link}\"/>");
+        let _ = writeln!(xml, "  <link rel=\"self\" href=\"{feed_url}\"/>");
+        let _ = writeln!(xml, "  <id>{link}</id>");
+        if let Some(updated) = self.updated() {
+            let _ = writeln!(xml, "  <updated>{}</updated>", rfc3339(updated));
+        }
+        for entry in &self.entries {
+            let page = entry.page;
+            // feeds only list dated pages, see `newest_pages`
+            let Some(published) = page.date else {
+                continue;
+            };
+            let link = escape(&permalink(base_url, &page.url));
+            let _ = writeln!(xml, "  <entry xml:base=\"{link}\">");
+            let _ = writeln!(xml, "    <title>{}</title>", escape(&page.title));
+            let _ = writeln!(xml, "    <link href=\"{link}\"/>");
+            let _ = writeln!(xml, "    <id>{link}</id>");
+            let _ = writeln!(xml, "    <published>{}</published>", rfc3339(published));
+            let _ = writeln!(
+                xml,
+                "    <updated>{}</updated>",
+                rfc3339(page.updated.unwrap_or(published).max(published))
+            );
+            if full_content {
+                let _ = writeln!(
+                    xml,
+                    "    <content type=\"html\">{}</content>",
+                    escape(&entry.html)
+                );
+            } else {
+                let _ = writeln!(
+                    xml,
+                    "    <summary type=\"html\">{}</summary>",
+                    escape(&entry.summary())
+                );
+            }
+            xml.push_str("  </entry>\n");
+        }
+        xml.push_str("</feed>\n");
+        xml
+    }
+
+    fn rss(&self, base_url: &str, full_content: bool) -> String {
+        let link = escape(&permalink(base_url, &self.dir));
+        let feed_url = escape(&permalink(base_url, &self.url(FeedFormat::Rss)));
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+        xml.push_str("<channel>\n");
+        let _ = writeln!(xml, "  <title>{}</title>", escape(&self.title));
+        let _ = writeln!(xml, "  <link>{link}</link>");
+        let _ = writeln!(xml, "  <description>{}</description>", escape(&self.title));
+        let _ = writeln!(
+            xml,
+            "  <atom:link href=\"{feed_url}\" rel=\"self\" type=\"application/rss+xml\"/>"
+        );
+        if let Some(updated) = self.updated() {
+            let _ = writeln!(xml, "  <lastBuildDate>{}</lastBuildDate>", rfc2822(updated));
+        }
+        for entry in &self.entries {
+            let page = entry.page;
+            let link = escape(&permalink(base_url, &page.url));
+            let description = if full_content {
+                entry.html.clone()
+            } else {
+                entry.summary()
+            };
+            xml.push_str("  <item>\n");
+            let _ = writeln!(xml, "    <title>{}</title>", escape(&page.title));
+            let _ = writeln!(xml, "    <link>{link}</link>");
+            let _ = writeln!(xml, "    <guid>{link}</guid>");
+            if let Some(date) = page.date {
+                let _ = writeln!(xml, "    <pubDate>{}</pubDate>", rfc2822(date));
+            }
+            let _ = writeln!(
+                xml,
+                "    <description>{}</description>",
+                escape(&description)
+            );
+            xml.push_str("  </item>\n");
+        }
+        xml.push_str("</channel>\n</rss>\n");
+        xml
+    }
+
+    /// A [JSON Feed 1.1](https://www.jsonfeed.org/version/1.1/).
+    fn json(&self, base_url: &str, full_content: bool) -> String {
+        let items: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let page = entry.page;
+                let link = permalink(base_url, &page.url);
+                let content = if full_content {
+                    entry.html.clone()
+                } else {
+                    entry.summary()
+                };
+                let mut item = json!({
+                    "id": link,
+                    "url": link,
+                    "title": page.title,
+                    "content_html": content,
+                    "date_published": page.date.map(rfc3339),
+                    "date_modified": page.updated.map(rfc3339),
+                });
+                let tags = page.front_matter.get_list("tags");
+                if !tags.is_empty() {
+                    item["tags"] = json!(tags);
+                }
+                item
+            })
+            .collect();
+        let feed = json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "home_page_url": permalink(base_url, &self.dir),
+            "feed_url": permalink(base_url, &self.url(FeedFormat::Json)),
+            "items": items,
+        });
+        serde_json::to_string_pretty(&feed).unwrap_or_default()
+    }
+}
+
+impl Entry<'_> {
+    /// The `summary` or `description` of the front matter, otherwise the
+    /// beginning of the content.
+    fn summary(&self) -> String {
+        let front_matter = &self.page.front_matter;
+        front_matter
+            .get_str("summary")
+            .or_else(|| front_matter.get_str("description"))
+            .unwrap_or_else(|| liquid_filters::truncate_words_html(&self.html, SUMMARY_WORDS, "…"))
+    }
+}
+
+/// Escapes text for use in xml elements and attributes.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn rfc3339(date: OffsetDateTime) -> String {
+    date.format(&Rfc3339).unwrap_or_default()
+}
+
+fn rfc2822(date: OffsetDateTime) -> String {
+    date.format(&Rfc2822).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::front_matter::FrontMatter;
+    use time::macros::datetime;
+
+    fn site_page(url: &str, date: Option<OffsetDateTime>) -> SitePage {
+        SitePage {
+            source: Path::new("blog").join(url),
+            url: format!("{url}/index.html"),
+            title: format!("{url} & more"),
+            section: Vec::new(),
+            depth: 1,
+            date,
+            updated: None,
+            front_matter: FrontMatter::default(),
+        }
+    }
+
+    #[test]
+    fn newest_dated_pages() {
+        let pages = [
+            site_page("Posts/a", Some(datetime!(2024-01-01 0:00 UTC))),
+            site_page("Posts/b", None),
+            site_page("Posts/c", Some(datetime!(2024-03-01 0:00 UTC))),
+            site_page("About", Some(datetime!(2024-04-01 0:00 UTC))),
+        ];
+        let urls = |pages: Vec<&SitePage>| {
+            pages
+                .iter()
+                .map(|page| page.url.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            urls(newest_pages(&pages, None, 2)),
+            ["About/index.html", "Posts/c/index.html"]
+        );
+        assert_eq!(
+            urls(newest_pages(&pages, Some(Path::new("blog/Posts")), 10)),
+            ["Posts/c/index.html", "Posts/a/index.html"]
+        );
+    }
+
+    #[test]
+    fn render_feeds() {
+        let page = site_page("Posts/Summer party", Some(datetime!(2024-03-01 12:00 UTC)));
+        let feed = Feed {
+            title: "Club".to_string(),
+            dir: String::new(),
+            entries: vec![Entry {
+                page: &page,
+                html: "<p>Save the date</p>".to_string(),
+            }],
+        };
+        let atom = feed.render(FeedFormat::Atom, "https://example.com", false);
+        assert!(atom.contains("<link rel=\"self\" href=\"https://example.com/atom.xml\"/>"));
+        assert!(atom.contains("<title>Posts/Summer party &amp; more</title>"));
+        assert!(atom.contains("<id>https://example.com/Posts/Summer%20party/index.html</id>"));
+        assert!(atom.contains("<updated>2024-03-01T12:00:00Z</updated>"));
+        assert!(atom.contains("<summary type=\"html\">&lt;p&gt;Save the date&lt;/p&gt;</summary>"));
+        let rss = feed.render(FeedFormat::Rss, "https://example.com", true);
+        assert!(rss.contains("<pubDate>Fri, 01 Mar 2024 12:00:00 +0000</pubDate>"));
+        assert!(rss.contains("<description>&lt;p&gt;Save the date&lt;/p&gt;</description>"));
+        let json: serde_json::Value =
+            serde_json::from_str(&feed.render(FeedFormat::Json, "https://example.com", true))
+                .unwrap();
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["feed_url"], "https://example.com/feed.json");
+        assert_eq!(json["items"][0]["date_published"], "2024-03-01T12:00:00Z");
+        assert_eq!(json["items"][0]["content_html"], "<p>Save the date</p>");
+    }
+
+    #[test]
+    fn empty_feeds_have_no_date() {
+        let feed = Feed {
+            title: "Club".to_string(),
+            dir: String::new(),
+            entries: Vec::new(),
+        };
+        let atom = feed.render(FeedFormat::Atom, "https://example.com", false);
+        assert!(!atom.contains("<updated>"));
+        let rss = feed.render(FeedFormat::Rss, "https://example.com", false);
+        assert!(!rss.contains("<lastBuildDate>"));
+    }
+}
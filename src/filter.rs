@@ -1,4 +1,9 @@
-use std::{path::Path, time::SystemTime};
+use crate::config::Config;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 use walkdir::{DirEntry, WalkDir};
 
 pub fn is_markdown(entry: &DirEntry) -> bool {
@@ -9,6 +14,95 @@ pub fn is_markdown(entry: &DirEntry) -> bool {
     })
 }
 
+/// The main markdown file of every directory of a project, see
+/// `index_markdown`. It is looked up once per build.
+#[derive(Debug, Default)]
+pub struct MainPages {
+    pages: HashMap<PathBuf, PathBuf>,
+}
+
+impl MainPages {
+    pub fn collect(path: &Path, conf: &Config) -> Self {
+        let pages = WalkDir::new(path)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || is_not_hidden(e))
+            .filter_map(Result::ok)
+            .filter(is_directory)
+            .filter_map(|dir| {
+                index_markdown(path, dir.path(), conf).map(|page| (dir.into_path(), page))
+            })
+            .collect();
+        Self { pages }
+    }
+
+    /// The main markdown file of a directory.
+    pub fn of(&self, dir: &Path) -> Option<&Path> {
+        self.pages.get(dir).map(PathBuf::as_path)
+    }
+
+    /// Tells whether a markdown file is the main page of its directory.
+    pub fn contains(&self, page: &Path) -> bool {
+        page.parent()
+            .and_then(|dir| self.of(dir))
+            .is_some_and(|main_page| main_page == page)
+    }
+}
+
+/// Tells whether a markdown file is the `404.md` page of the project root,
+/// which is shown for missing pages and kept out of the navigation.
+pub fn is_not_found_markdown(path: &Path, page: &Path) -> bool {
+    page.parent() == Some(path) && page.file_name().is_some_and(|name| name == "404.md")
+}
+
+/// Returns the main markdown file of a directory of the project at `path`.
+/// That is `index.md`, otherwise `gallery.md`, otherwise the first markdown
+/// file in alphabetical order. The `404.md` of the project root never is.
+/// The directory of a posts section has no main page but its `index.md`.
+fn index_markdown(path: &Path, dir: &Path, conf: &Config) -> Option<PathBuf> {
+    let markdown_files = markdown_files(dir);
+    let find = |name| find_markdown(&markdown_files, name);
+    let is_section = conf
+        .sections
+        .iter()
+        .flatten()
+        .any(|section| section.dir(path) == dir);
+    if is_section {
+        return find("index.md").cloned();
+    }
+    find("index.md")
+        .or_else(|| find("gallery.md"))
+        .or_else(|| {
+            markdown_files
+                .iter()
+                .find(|f| !is_not_found_markdown(path, f))
+        })
+        .cloned()
+}
+
+/// Returns the `index.md` of a directory.
+pub fn find_index_md(dir: &Path) -> Option<PathBuf> {
+    find_markdown(&markdown_files(dir), "index.md").cloned()
+}
+
+/// The visible markdown files of a directory in alphabetical order.
+fn markdown_files(dir: &Path) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| is_markdown(e) && is_not_hidden(e))
+        .map(DirEntry::into_path)
+        .collect()
+}
+
+fn find_markdown<'a>(markdown_files: &'a [PathBuf], name: &str) -> Option<&'a PathBuf> {
+    markdown_files
+        .iter()
+        .find(|f| f.file_name().is_some_and(|f| f.eq_ignore_ascii_case(name)))
+}
+
 pub fn is_modified_markdown(entry: &DirEntry, last_build: SystemTime) -> bool {
     let is_markdown = is_markdown(entry);
     if is_markdown
@@ -28,7 +122,7 @@ pub fn is_not_hidden(entry: &DirEntry) -> bool {
 }
 
 pub fn is_directory(entry: &DirEntry) -> bool {
-    entry.metadata().map(|s| s.is_dir()).unwrap_or(false)
+    entry.metadata().is_ok_and(|s| s.is_dir())
 }
 
 pub fn is_image(entry: &DirEntry) -> bool {
@@ -47,16 +141,33 @@ pub fn contains_markdown_file(entry: &DirEntry) -> bool {
         .any(|e| e.is_ok() && is_markdown(e.as_ref().unwrap()))
 }
 
-pub fn contains_markdown_in_dir(entry: &DirEntry) -> bool {
-    WalkDir::new(entry.path())
-        .max_depth(1)
-        .into_iter()
-        .any(|e| e.is_ok_and(|e| is_markdown(&e)))
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_project::TestProject;
 
-pub fn contains_markdown_subdir(entry: &DirEntry) -> bool {
-    WalkDir::new(entry.path())
-        .min_depth(1)
-        .into_iter()
-        .any(|e| e.is_ok_and(|e| is_directory(&e) && contains_markdown_file(&e)))
+    #[test]
+    fn not_found_page_is_no_index() {
+        let project = TestProject::new("not_found_index");
+        project
+            .write("404.md", "# Not found")
+            .write("about.md", "# About")
+            .write("Rules/404.md", "# Rule 404");
+        let path = project.path();
+        let conf = Config::default();
+        assert_eq!(
+            index_markdown(path, path, &conf),
+            Some(path.join("about.md"))
+        );
+        assert_eq!(
+            index_markdown(path, &path.join("Rules"), &conf),
+            Some(path.join("Rules/404.md"))
+        );
+        let main_pages = MainPages::collect(path, &conf);
+        assert!(main_pages.contains(&path.join("about.md")));
+        assert!(main_pages.contains(&path.join("Rules/404.md")));
+        assert!(!main_pages.contains(&path.join("404.md")));
+        std::fs::remove_file(path.join("about.md")).unwrap();
+        assert_eq!(index_markdown(path, path, &conf), None);
+    }
 }
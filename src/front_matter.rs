@@ -0,0 +1,263 @@
+use anyhow::{Context, Result};
+use liquid::ValueView;
+use liquid::model::{KString, Value};
+use time::format_description::FormatItem;
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+static TOML_DELIMITER: &str = "+++";
+static YAML_DELIMITER: &str = "---";
+static DATE: &[FormatItem] = format_description!("[year]-[month]-[day]");
+static DATE_TIME: &[FormatItem] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+static DATE_TIME_T: &[FormatItem] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]");
+
+/// Metadata of a markdown page taken from an optional `+++` (TOML) or
+/// `---` (YAML) header at the very beginning of the file.
+#[derive(Debug, Clone, Default)]
+pub struct FrontMatter {
+    fields: liquid::model::Object,
+}
+
+impl FrontMatter {
+    pub fn to_liquid(&self) -> Value {
+        Value::Object(self.fields.clone())
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<String> {
+        self.fields
+            .get(key)
+            .and_then(ValueView::as_scalar)
+            .map(|value| value.into_string().to_string())
+    }
+
+    /// Reads a list of strings like `tags = ["news", "club"]`. A single string
+    /// is a list of one.
+    pub fn get_list(&self, key: &str) -> Vec<String> {
+        let Some(value) = self.fields.get(key) else {
+            return Vec::new();
+        };
+        value.as_array().map_or_else(
+            || {
+                value
+                    .as_scalar()
+                    .map(|item| vec![item.into_string().to_string()])
+                    .unwrap_or_default()
+            },
+            |array| {
+                array
+                    .values()
+                    .filter_map(ValueView::as_scalar)
+                    .map(|item| item.into_string().to_string())
+                    .collect()
+            },
+        )
+    }
+
+    /// Reads a date field, see [`parse_date`]. Missing fields yield `None`.
+    pub fn date(&self, key: &str, offset: UtcOffset) -> Result<Option<OffsetDateTime>> {
+        let Some(value) = self.fields.get(key).and_then(ValueView::as_scalar) else {
+            return Ok(None);
+        };
+        parse_date(value.to_kstr().as_str(), offset)
+            .map(Some)
+            .with_context(|| format!("invalid {key} '{}'", value.to_kstr()))
+    }
+
+    /// Pages with `draft = true` are only built with `--drafts`.
+    pub fn is_draft(&self) -> bool {
+        self.flag("draft")
+    }
+
+    /// Pages with `hidden = true` are rendered but left out of the sitemap.
+    pub fn is_hidden(&self) -> bool {
+        self.flag("hidden")
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.fields
+            .get(key)
+            .and_then(ValueView::as_scalar)
+            .and_then(|flag| flag.to_bool())
+            .unwrap_or(false)
+    }
+}
+
+/// Splits `input` into its front matter and the remaining markdown body.
+///
+/// Files without a header yield an empty front matter and the unchanged input.
+pub fn split(input: &str) -> Result<(FrontMatter, &str)> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let Some((delimiter, rest)) = [TOML_DELIMITER, YAML_DELIMITER]
+        .into_iter()
+        .find_map(|d| strip_delimiter_line(input, d).map(|rest| (d, rest)))
+    else {
+        return Ok((FrontMatter::default(), input));
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            let header = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            let fields = if delimiter == TOML_DELIMITER {
+                parse_toml(header)?
+            } else {
+                parse_yaml(header)?
+            };
+            return Ok((FrontMatter { fields }, body));
+        }
+        offset += line.len();
+    }
+    anyhow::bail!("front matter is not terminated by a closing '{delimiter}' line")
+}
+
+/// Parses RFC 3339 timestamps as well as `YYYY-MM-DD HH:MM:SS` and plain
+/// `YYYY-MM-DD` dates. Dates without an offset are taken to be in `offset`,
+/// plain dates start at midnight.
+pub fn parse_date(date: &str, offset: UtcOffset) -> Result<OffsetDateTime> {
+    let date = date.trim();
+    if let Ok(date_time) = OffsetDateTime::parse(date, &Rfc3339) {
+        return Ok(date_time);
+    }
+    let date_time = PrimitiveDateTime::parse(date, DATE_TIME)
+        .or_else(|_| PrimitiveDateTime::parse(date, DATE_TIME_T))
+        .or_else(|_| Date::parse(date, DATE).map(Date::midnight))?;
+    Ok(date_time.assume_offset(offset))
+}
+
+fn strip_delimiter_line<'a>(input: &'a str, delimiter: &str) -> Option<&'a str> {
+    let rest = input.strip_prefix(delimiter)?;
+    let (line, rest) = rest.split_once('\n').unwrap_or((rest, ""));
+    line.trim().is_empty().then_some(rest)
+}
+
+fn parse_toml(header: &str) -> Result<liquid::model::Object> {
+    let table =
+        toml::from_str::<toml::Table>(header).context("parsing TOML front matter failed")?;
+    Ok(table
+        .into_iter()
+        .map(|(key, value)| (KString::from(key), toml_to_liquid(value)))
+        .collect())
+}
+
+fn parse_yaml(header: &str) -> Result<liquid::model::Object> {
+    if header.trim().is_empty() {
+        return Ok(liquid::model::Object::new());
+    }
+    serde_yaml::from_str(header).context("parsing YAML front matter failed")
+}
+
+/// Converts a TOML value into its liquid counterpart. Dates are kept as strings.
+pub fn toml_to_liquid(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::scalar(s),
+        toml::Value::Integer(i) => Value::scalar(i),
+        toml::Value::Float(f) => Value::scalar(f),
+        toml::Value::Boolean(b) => Value::scalar(b),
+        toml::Value::Datetime(d) => Value::scalar(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(toml_to_liquid).collect()),
+        toml::Value::Table(t) => Value::Object(
+            t.into_iter()
+                .map(|(key, value)| (KString::from(key), toml_to_liquid(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(front_matter: &FrontMatter, key: &str) -> Option<String> {
+        front_matter
+            .fields
+            .get(key)
+            .and_then(ValueView::as_scalar)
+            .map(|s| s.to_kstr().to_string())
+    }
+
+    #[test]
+    fn no_front_matter() {
+        let (front_matter, body) = split("# Title\n\nText").unwrap();
+        assert!(front_matter.fields.is_empty());
+        assert_eq!(body, "# Title\n\nText");
+    }
+
+    #[test]
+    fn toml_front_matter() {
+        let input = "+++\ntitle = \"Feeding\"\ndate = 2024-03-01\n+++\n# Content\n";
+        let (front_matter, body) = split(input).unwrap();
+        assert_eq!(field(&front_matter, "title").as_deref(), Some("Feeding"));
+        assert_eq!(field(&front_matter, "date").as_deref(), Some("2024-03-01"));
+        assert_eq!(body, "# Content\n");
+    }
+
+    #[test]
+    fn yaml_front_matter() {
+        let input = "---\r\ntitle: Training\r\ntags: [a, b]\r\n---\r\nText";
+        let (front_matter, body) = split(input).unwrap();
+        assert_eq!(field(&front_matter, "title").as_deref(), Some("Training"));
+        assert_eq!(
+            front_matter
+                .fields
+                .get("tags")
+                .and_then(ValueView::as_array)
+                .map(liquid::model::ArrayView::size),
+            Some(2)
+        );
+        assert_eq!(body, "Text");
+    }
+
+    #[test]
+    fn draft_flag() {
+        let (front_matter, _) = split("+++\ndraft = true\n+++\n").unwrap();
+        assert!(front_matter.is_draft());
+        let (front_matter, _) = split("---\ndraft: false\n---\n").unwrap();
+        assert!(!front_matter.is_draft());
+        let (front_matter, _) = split("Text").unwrap();
+        assert!(!front_matter.is_draft());
+        let (front_matter, _) = split("+++\nhidden = true\n+++\n").unwrap();
+        assert!(front_matter.is_hidden() && !front_matter.is_draft());
+    }
+
+    #[test]
+    fn lists() {
+        let (front_matter, _) =
+            split("+++\ntags = [\"news\", \"club\"]\ncategories = \"diving\"\n+++\n").unwrap();
+        assert_eq!(front_matter.get_list("tags"), ["news", "club"]);
+        assert_eq!(front_matter.get_list("categories"), ["diving"]);
+        assert!(front_matter.get_list("authors").is_empty());
+    }
+
+    #[test]
+    fn dates() {
+        let offset = UtcOffset::from_hms(2, 0, 0).unwrap();
+        let input = "+++\npublish_date = 2024-03-01\nexpiry_date = 2024-03-02T10:30:00Z\n+++\n";
+        let (front_matter, _) = split(input).unwrap();
+        let publish_date = front_matter.date("publish_date", offset).unwrap().unwrap();
+        assert_eq!(publish_date.to_string(), "2024-03-01 0:00:00.0 +02:00:00");
+        let expiry_date = front_matter.date("expiry_date", offset).unwrap().unwrap();
+        assert_eq!(expiry_date.to_string(), "2024-03-02 10:30:00.0 +00:00:00");
+        assert!(front_matter.date("date", offset).unwrap().is_none());
+        assert_eq!(
+            parse_date("2024-03-01 08:15:00", offset)
+                .unwrap()
+                .to_string(),
+            "2024-03-01 8:15:00.0 +02:00:00"
+        );
+        assert!(parse_date("March 1st", offset).is_err());
+    }
+
+    #[test]
+    fn thematic_break_is_no_front_matter() {
+        let (_, body) = split("---- \nText").unwrap();
+        assert_eq!(body, "---- \nText");
+    }
+
+    #[test]
+    fn unterminated_front_matter() {
+        assert!(split("+++\ntitle = \"x\"\n").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use crate::config::Config;
+use crate::feed;
+use crate::site::{Site, SitePage};
+use serde_json::{Value, json};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+/// The site level index of all pages.
+pub static INDEX_JSON: &str = "index.json";
+
+/// The json export of the home page, whose `index.json` is the site level
+/// index. Markdown files starting with `_` are hidden, so no other page is
+/// exported under this name.
+pub static HOME_JSON: &str = "_index.json";
+
+/// Elements that end a line of the plain text of a page.
+static BLOCK_ELEMENTS: [&str; 22] = [
+    "article",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dt",
+    "figcaption",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// The path of the json file of a page, e.g. `Posts/index.json` for
+/// `Posts/index.html`. The home page has the `HOME_JSON`.
+pub fn json_url(url: &str) -> String {
+    if url == "index.html" {
+        return HOME_JSON.to_owned();
+    }
+    format!("{}.json", url.strip_suffix(".html").unwrap_or(url))
+}
+
+/// Everything known about a page without rendering it.
+fn page_entry(page: &SitePage, base_url: Option<&str>) -> serde_json::Map<String, Value> {
+    let date = |date: Option<OffsetDateTime>| {
+        date.and_then(|date| date.format(&Rfc3339).ok())
+            .map_or(Value::Null, Value::String)
+    };
+    let mut entry = serde_json::Map::new();
+    entry.insert("title".into(), page.title.clone().into());
+    entry.insert("url".into(), page.url.clone().into());
+    if let Some(base_url) = base_url {
+        entry.insert(
+            "permalink".into(),
+            feed::permalink(base_url, &page.url).into(),
+        );
+    }
+    entry.insert("json_url".into(), json_url(&page.url).into());
+    entry.insert("section".into(), page.section.clone().into());
+    entry.insert("date".into(), date(page.date));
+    entry.insert("updated".into(), date(page.updated));
+    entry.insert(
+        "metadata".into(),
+        serde_json::to_value(page.front_matter.to_liquid()).unwrap_or_default(),
+    );
+    entry
+}
+
+/// The json export of a rendered page with its content as html and as plain
+/// text.
+pub fn page(page: &SitePage, html: &str, base_url: Option<&str>) -> Value {
+    let mut entry = page_entry(page, base_url);
+    entry.insert("html".into(), html.into());
+    entry.insert("text".into(), plain_text(html).into());
+    Value::Object(entry)
+}
+
+/// The `index.json` listing every page of the site.
+pub fn site_index(site: &Site, conf: &Config) -> Value {
+    let pages: Vec<_> = site
+        .pages
+        .iter()
+        .map(|page| Value::Object(page_entry(page, conf.base_url.as_deref())))
+        .collect();
+    json!({
+        "title": conf.title,
+        "base_url": conf.base_url,
+        "pages": pages,
+    })
+}
+
+/// Strips the tags of `html`. Block elements end a line, empty lines are
+/// dropped.
+pub fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |end| start + end + 1);
+        let name = rest[start..end]
+            .trim_start_matches(['<', '/'])
+            .chars()
+            .take_while(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if BLOCK_ELEMENTS.contains(&name.as_str()) {
+            text.push('\n');
+        }
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&");
+    text.lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_to_plain_text() {
+        let html = "<h1 id=\"party\">Summer party</h1>\n<p>Bring <em>food</em> &amp; drinks</p>\n\
+                    <ul>\n<li>Salad</li>\n<li>Cake</li>\n</ul>\n";
+        assert_eq!(
+            plain_text(html),
+            "Summer party\nBring food & drinks\nSalad\nCake"
+        );
+    }
+
+    #[test]
+    fn json_urls() {
+        assert_eq!(json_url("Posts/index.html"), "Posts/index.json");
+        assert_eq!(json_url("about.html"), "about.json");
+        assert_eq!(json_url("index.html"), "_index.json");
+    }
+}
@@ -0,0 +1,951 @@
+use crate::config::{Config, Markdown};
+use crate::front_matter;
+use crate::markdown;
+use liquid_core::model::{ScalarCow, ValueCow};
+use liquid_core::parser::FilterArguments;
+use liquid_core::{
+    Display_filter, Error, Expression, Filter, FilterParameters, FilterReflection,
+    FromFilterParameters, ParseFilter, Result, Runtime, Value, ValueView,
+};
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+/// Adds the site building filters to `builder`. Filters of the standard
+/// library with the same name (`date`, `where` and `sort`) are replaced.
+pub fn register(
+    builder: liquid::ParserBuilder,
+    path: &Path,
+    conf: &Config,
+) -> liquid::ParserBuilder {
+    builder
+        .filter(Date {
+            locale: conf.locale.clone().unwrap_or_else(|| "en".to_string()),
+        })
+        .filter(Slugify)
+        .filter(Markdownify {
+            settings: conf
+                .markdown
+                .clone()
+                .expect("Invalid config: expected markdown settings are missing"),
+        })
+        .filter(RelativeUrl)
+        .filter(AbsoluteUrl {
+            base_url: conf.base_url.clone(),
+        })
+        .filter(AssetUrl {
+            path: path.to_path_buf(),
+        })
+        .filter(TruncateWordsHtml)
+        .filter(Where)
+        .filter(Sort)
+}
+
+fn invalid_input(cause: impl Into<String>) -> Error {
+    Error::with_msg("Invalid input").context("cause", cause.into())
+}
+
+fn no_arguments(name: &str, mut arguments: FilterArguments) -> Result<()> {
+    if arguments.positional.next().is_some() || arguments.keyword.next().is_some() {
+        return Err(Error::with_msg(format!("{name} takes no arguments")));
+    }
+    Ok(())
+}
+
+/// Looks up a dotted path like `page.title` in an object.
+fn property<'a>(value: &'a dyn ValueView, path: &str) -> Option<&'a dyn ValueView> {
+    path.split('.').try_fold(value, |value, key| {
+        value.as_object().and_then(|object| object.get(key))
+    })
+}
+
+fn root_dir(runtime: &dyn Runtime) -> String {
+    runtime
+        .try_get(&[ScalarCow::new("root_dir")])
+        .map(|root_dir| root_dir.to_kstr().to_string())
+        .unwrap_or_default()
+}
+
+/// A path of the site relative to the current page.
+fn relative_url(runtime: &dyn Runtime, url: &str) -> String {
+    format!("{}{}", root_dir(runtime), url.trim_start_matches('/'))
+}
+
+/// Links with a scheme, anchors and protocol relative urls are left alone.
+fn is_external(url: &str) -> bool {
+    url.contains("://")
+        || url.starts_with('#')
+        || url.starts_with("//")
+        || url.starts_with("mailto:")
+}
+
+#[derive(Debug, FilterParameters)]
+struct DateArgs {
+    #[parameter(description = "The strftime format of the date.", arg_type = "str")]
+    format: Expression,
+    #[parameter(
+        description = "The locale of month and weekday names, e.g. \"de\".",
+        arg_type = "str"
+    )]
+    locale: Option<Expression>,
+}
+
+#[derive(Clone, FilterReflection)]
+#[filter(
+    name = "date",
+    description = "Formats a date with month and weekday names of the site locale.",
+    parameters(DateArgs),
+    parsed(DateFilter)
+)]
+pub struct Date {
+    locale: String,
+}
+
+impl ParseFilter for Date {
+    fn parse(&self, arguments: FilterArguments) -> Result<Box<dyn Filter>> {
+        let args = DateArgs::from_args(arguments)?;
+        Ok(Box::new(DateFilter {
+            args,
+            locale: self.locale.clone(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug, Display_filter)]
+#[name = "date"]
+struct DateFilter {
+    #[parameters]
+    args: DateArgs,
+    locale: String,
+}
+
+impl Filter for DateFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let args = self.args.evaluate(runtime)?;
+        let Some(scalar) = input.as_scalar() else {
+            return Ok(input.to_value());
+        };
+        let date = front_matter::parse_date(scalar.to_kstr().as_str(), time::UtcOffset::UTC)
+            .ok()
+            .or_else(|| scalar.to_date_time().map(|date| *date));
+        let Some(date) = date else {
+            return Ok(input.to_value());
+        };
+        let locale_name = args.locale.as_deref().unwrap_or(self.locale.as_str());
+        let locale = Locale::find(locale_name)
+            .ok_or_else(|| Error::with_msg(format!("Unknown locale: {locale_name}")))?;
+        format_date(date, &args.format, locale).map(Value::scalar)
+    }
+}
+
+/// Month and weekday names used by the `date` filter.
+struct Locale {
+    name: &'static str,
+    months: [&'static str; 12],
+    weekdays: [&'static str; 7],
+}
+
+static LOCALES: [Locale; 6] = [
+    Locale {
+        name: "en",
+        months: [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ],
+        weekdays: [
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+            "Sunday",
+        ],
+    },
+    Locale {
+        name: "de",
+        months: [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ],
+        weekdays: [
+            "Montag",
+            "Dienstag",
+            "Mittwoch",
+            "Donnerstag",
+            "Freitag",
+            "Samstag",
+            "Sonntag",
+        ],
+    },
+    Locale {
+        name: "fr",
+        months: [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        weekdays: [
+            "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+        ],
+    },
+    Locale {
+        name: "es",
+        months: [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+        weekdays: [
+            "lunes",
+            "martes",
+            "miércoles",
+            "jueves",
+            "viernes",
+            "sábado",
+            "domingo",
+        ],
+    },
+    Locale {
+        name: "it",
+        months: [
+            "gennaio",
+            "febbraio",
+            "marzo",
+            "aprile",
+            "maggio",
+            "giugno",
+            "luglio",
+            "agosto",
+            "settembre",
+            "ottobre",
+            "novembre",
+            "dicembre",
+        ],
+        weekdays: [
+            "lunedì",
+            "martedì",
+            "mercoledì",
+            "giovedì",
+            "venerdì",
+            "sabato",
+            "domenica",
+        ],
+    },
+    Locale {
+        name: "nl",
+        months: [
+            "januari",
+            "februari",
+            "maart",
+            "april",
+            "mei",
+            "juni",
+            "juli",
+            "augustus",
+            "september",
+            "oktober",
+            "november",
+            "december",
+        ],
+        weekdays: [
+            "maandag",
+            "dinsdag",
+            "woensdag",
+            "donderdag",
+            "vrijdag",
+            "zaterdag",
+            "zondag",
+        ],
+    },
+];
+
+impl Locale {
+    /// Finds a locale by its language, `de`, `de-DE` and `de_DE` all select German.
+    fn find(name: &str) -> Option<&'static Self> {
+        let language = name.split(['-', '_']).next().unwrap_or(name);
+        LOCALES
+            .iter()
+            .find(|locale| locale.name.eq_ignore_ascii_case(language))
+    }
+}
+
+fn abbreviate(name: &str) -> String {
+    name.chars().take(3).collect()
+}
+
+/// Formats `date` like strftime. A `-` after the `%` removes the padding of
+/// numbers, e.g. `%-d`.
+fn format_date(date: OffsetDateTime, format: &str, locale: &Locale) -> Result<String> {
+    let mut output = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+        let mut spec = chars.next();
+        let pad = spec != Some('-');
+        if !pad {
+            spec = chars.next();
+        }
+        let number = |value: i64, width: usize| {
+            if pad {
+                format!("{value:0width$}")
+            } else {
+                value.to_string()
+            }
+        };
+        let month = usize::from(u8::from(date.month())) - 1;
+        let weekday = usize::from(date.weekday().number_days_from_monday());
+        let hour12 = match date.hour() % 12 {
+            0 => 12,
+            hour => hour,
+        };
+        let formatted = match spec {
+            Some('Y') => date.year().to_string(),
+            Some('C') => number(i64::from(date.year() / 100), 2),
+            Some('y') => number(i64::from(date.year() % 100), 2),
+            Some('m') => number(i64::from(u8::from(date.month())), 2),
+            Some('B') => locale.months[month].to_string(),
+            Some('b' | 'h') => abbreviate(locale.months[month]),
+            Some('d') => number(i64::from(date.day()), 2),
+            Some('e') => format!("{:>2}", date.day()),
+            Some('j') => number(i64::from(date.ordinal()), 3),
+            Some('A') => locale.weekdays[weekday].to_string(),
+            Some('a') => abbreviate(locale.weekdays[weekday]),
+            Some('u') => (weekday + 1).to_string(),
+            Some('w') => ((weekday + 1) % 7).to_string(),
+            Some('H') => number(i64::from(date.hour()), 2),
+            Some('I') => number(i64::from(hour12), 2),
+            Some('M') => number(i64::from(date.minute()), 2),
+            Some('S') => number(i64::from(date.second()), 2),
+            Some('p') => if date.hour() < 12 { "AM" } else { "PM" }.to_string(),
+            Some('P') => if date.hour() < 12 { "am" } else { "pm" }.to_string(),
+            Some('F') => format_date(date, "%Y-%m-%d", locale)?,
+            Some('D') => format_date(date, "%m/%d/%y", locale)?,
+            Some('T') => format_date(date, "%H:%M:%S", locale)?,
+            Some('R') => format_date(date, "%H:%M", locale)?,
+            Some('z') => {
+                let (hours, minutes, _) = date.offset().as_hms();
+                let sign = if date.offset().is_negative() {
+                    '-'
+                } else {
+                    '+'
+                };
+                format!("{sign}{:02}{:02}", hours.abs(), minutes.abs())
+            }
+            Some('s') => date.unix_timestamp().to_string(),
+            Some('%') => "%".to_string(),
+            Some(other) => {
+                return Err(Error::with_msg(format!(
+                    "Invalid date-format string: unknown directive %{other}"
+                )));
+            }
+            None => {
+                return Err(Error::with_msg(
+                    "Invalid date-format string: trailing %".to_string(),
+                ));
+            }
+        };
+        output.push_str(&formatted);
+    }
+    Ok(output)
+}
+
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "slugify",
+    description = "Turns a text into a lowercase url slug.",
+    parsed(SlugifyFilter)
+)]
+pub struct Slugify;
+
+#[derive(Debug, Default, Display_filter)]
+#[name = "slugify"]
+struct SlugifyFilter;
+
+impl Filter for SlugifyFilter {
+    fn evaluate(&self, input: &dyn ValueView, _runtime: &dyn Runtime) -> Result<Value> {
+        Ok(Value::scalar(markdown::slugify(input.to_kstr().as_str())))
+    }
+}
+
+#[derive(Clone, FilterReflection)]
+#[filter(
+    name = "markdownify",
+    description = "Converts markdown into html.",
+    parsed(MarkdownifyFilter)
+)]
+pub struct Markdownify {
+    settings: Markdown,
+}
+
+impl ParseFilter for Markdownify {
+    fn parse(&self, arguments: FilterArguments) -> Result<Box<dyn Filter>> {
+        no_arguments("markdownify", arguments)?;
+        Ok(Box::new(MarkdownifyFilter {
+            settings: self.settings.clone(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug, Display_filter)]
+#[name = "markdownify"]
+struct MarkdownifyFilter {
+    settings: Markdown,
+}
+
+impl Filter for MarkdownifyFilter {
+    fn evaluate(&self, input: &dyn ValueView, _runtime: &dyn Runtime) -> Result<Value> {
+        if input.is_nil() {
+            return Ok(Value::Nil);
+        }
+        let rendered =
+            markdown::convert_markdown_to_html(input.to_kstr().as_str(), &self.settings, None);
+        Ok(Value::scalar(rendered.html))
+    }
+}
+
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "relative_url",
+    description = "Prefixes a path of the site with the root_dir of the current page.",
+    parsed(RelativeUrlFilter)
+)]
+pub struct RelativeUrl;
+
+#[derive(Debug, Default, Display_filter)]
+#[name = "relative_url"]
+struct RelativeUrlFilter;
+
+impl Filter for RelativeUrlFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let url = input.to_kstr();
+        if is_external(url.as_str()) {
+            return Ok(input.to_value());
+        }
+        Ok(Value::scalar(relative_url(runtime, url.as_str())))
+    }
+}
+
+#[derive(Clone, FilterReflection)]
+#[filter(
+    name = "absolute_url",
+    description = "Prefixes a path of the site with the base_url of config.toml. \
+                   Without a base_url it works like relative_url.",
+    parsed(AbsoluteUrlFilter)
+)]
+pub struct AbsoluteUrl {
+    base_url: Option<String>,
+}
+
+impl ParseFilter for AbsoluteUrl {
+    fn parse(&self, arguments: FilterArguments) -> Result<Box<dyn Filter>> {
+        no_arguments("absolute_url", arguments)?;
+        Ok(Box::new(AbsoluteUrlFilter {
+            base_url: self.base_url.clone(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug, Display_filter)]
+#[name = "absolute_url"]
+struct AbsoluteUrlFilter {
+    base_url: Option<String>,
+}
+
+impl Filter for AbsoluteUrlFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let url = input.to_kstr();
+        if is_external(url.as_str()) {
+            return Ok(input.to_value());
+        }
+        Ok(Value::scalar(self.base_url.as_deref().map_or_else(
+            || relative_url(runtime, url.as_str()),
+            |base_url| absolute_url(base_url, url.as_str()),
+        )))
+    }
+}
+
+/// Joins the `base_url` of the site and a path relative to the site root.
+pub fn absolute_url(base_url: &str, url: &str) -> String {
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        url.trim_start_matches("./").trim_start_matches('/')
+    )
+}
+
+#[derive(Clone, FilterReflection)]
+#[filter(
+    name = "asset_url",
+    description = "Like relative_url, but adds a hash of the file content to bust caches.",
+    parsed(AssetUrlFilter)
+)]
+pub struct AssetUrl {
+    path: PathBuf,
+}
+
+impl ParseFilter for AssetUrl {
+    fn parse(&self, arguments: FilterArguments) -> Result<Box<dyn Filter>> {
+        no_arguments("asset_url", arguments)?;
+        Ok(Box::new(AssetUrlFilter {
+            path: self.path.clone(),
+        }))
+    }
+
+    fn reflection(&self) -> &dyn FilterReflection {
+        self
+    }
+}
+
+#[derive(Debug, Display_filter)]
+#[name = "asset_url"]
+struct AssetUrlFilter {
+    path: PathBuf,
+}
+
+impl Filter for AssetUrlFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let url = input.to_kstr();
+        if is_external(url.as_str()) {
+            return Ok(input.to_value());
+        }
+        let url = url.trim_start_matches('/');
+        let asset = std::fs::read(self.path.join(url))
+            .map_err(|err| invalid_input(format!("Asset {url} could not be read: {err}")))?;
+        let hash = Sha1::digest(&asset);
+        let version = hash[..4].iter().fold(String::new(), |mut version, byte| {
+            let _ = write!(version, "{byte:02x}");
+            version
+        });
+        Ok(Value::scalar(format!(
+            "{}{url}?v={version}",
+            root_dir(runtime)
+        )))
+    }
+}
+
+#[derive(Debug, FilterParameters)]
+struct TruncateWordsHtmlArgs {
+    #[parameter(description = "The number of words to keep.", arg_type = "integer")]
+    words: Expression,
+    #[parameter(description = "Appended if the html was truncated.", arg_type = "str")]
+    ellipsis: Option<Expression>,
+}
+
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "truncate_words_html",
+    description = "Truncates html to a number of words and closes all open tags.",
+    parameters(TruncateWordsHtmlArgs),
+    parsed(TruncateWordsHtmlFilter)
+)]
+pub struct TruncateWordsHtml;
+
+#[derive(Debug, FromFilterParameters, Display_filter)]
+#[name = "truncate_words_html"]
+struct TruncateWordsHtmlFilter {
+    #[parameters]
+    args: TruncateWordsHtmlArgs,
+}
+
+impl Filter for TruncateWordsHtmlFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let args = self.args.evaluate(runtime)?;
+        let words = usize::try_from(args.words)
+            .map_err(|_| invalid_input("The number of words must not be negative"))?;
+        let ellipsis = args.ellipsis.as_deref().unwrap_or("…");
+        Ok(Value::scalar(truncate_words_html(
+            input.to_kstr().as_str(),
+            words,
+            ellipsis,
+        )))
+    }
+}
+
+static VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+pub fn truncate_words_html(html: &str, max_words: usize, ellipsis: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut open_tags: Vec<String> = Vec::new();
+    let mut words = 0;
+    let mut in_word = false;
+    let mut rest = html;
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            let tag = &rest[..end];
+            let name: String = tag
+                .trim_start_matches(['<', '/'])
+                .chars()
+                .take_while(char::is_ascii_alphanumeric)
+                .collect::<String>()
+                .to_ascii_lowercase();
+            if tag.starts_with("</") {
+                if let Some(pos) = open_tags.iter().rposition(|open| *open == name) {
+                    open_tags.truncate(pos);
+                }
+            } else if !name.is_empty()
+                && !tag.ends_with("/>")
+                && !VOID_ELEMENTS.contains(&name.as_str())
+            {
+                open_tags.push(name);
+            }
+            output.push_str(tag);
+            rest = &rest[end..];
+            in_word = false;
+            continue;
+        }
+        if c.is_whitespace() {
+            in_word = false;
+        } else if !in_word {
+            in_word = true;
+            words += 1;
+            if words > max_words {
+                output.truncate(output.trim_end().len());
+                output.push_str(ellipsis);
+                for tag in open_tags.iter().rev() {
+                    let _ = write!(output, "</{tag}>");
+                }
+                return output;
+            }
+        }
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    output
+}
+
+#[derive(Debug, FilterParameters)]
+struct WhereArgs {
+    #[parameter(
+        description = "The property being matched, may be a path like `page.category`.",
+        arg_type = "str"
+    )]
+    property: Expression,
+    #[parameter(
+        description = "The value the property is matched with. By default any truthy value matches.",
+        arg_type = "any"
+    )]
+    target_value: Option<Expression>,
+}
+
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "where",
+    description = "Keeps the objects of an array whose property matches a value. \
+                   Array properties match if they contain the value.",
+    parameters(WhereArgs),
+    parsed(WhereFilter)
+)]
+pub struct Where;
+
+#[derive(Debug, FromFilterParameters, Display_filter)]
+#[name = "where"]
+struct WhereFilter {
+    #[parameters]
+    args: WhereArgs,
+}
+
+fn matches(value: &dyn ValueView, target: Option<&ValueCow<'_>>) -> bool {
+    match (value.as_array(), target) {
+        (Some(array), Some(_)) => array.values().any(|item| matches(item, target)),
+        (_, Some(target)) => value.is_scalar() && value.to_kstr() == target.to_kstr(),
+        (_, None) => value.query_state(liquid_core::model::State::Truthy),
+    }
+}
+
+impl Filter for WhereFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let args = self.args.evaluate(runtime)?;
+        let array = input
+            .as_array()
+            .ok_or_else(|| invalid_input("Array expected"))?;
+        let matching = array
+            .values()
+            .filter(|item| {
+                property(*item, &args.property)
+                    .is_some_and(|value| matches(value, args.target_value.as_ref()))
+            })
+            .map(ValueView::to_value)
+            .collect();
+        Ok(Value::Array(matching))
+    }
+}
+
+#[derive(Debug, FilterParameters)]
+struct SortArgs {
+    #[parameter(
+        description = "The property to sort by, may be a path like `page.date`.",
+        arg_type = "str"
+    )]
+    property: Option<Expression>,
+    #[parameter(description = "`asc` (default) or `desc`.", arg_type = "str")]
+    order: Option<Expression>,
+}
+
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "sort",
+    description = "Sorts an array, optionally by a property of its objects. Missing values come last.",
+    parameters(SortArgs),
+    parsed(SortFilter)
+)]
+pub struct Sort;
+
+#[derive(Debug, FromFilterParameters, Display_filter)]
+#[name = "sort"]
+struct SortFilter {
+    #[parameters]
+    args: SortArgs,
+}
+
+fn compare(a: Option<&dyn ValueView>, b: Option<&dyn ValueView>) -> Ordering {
+    let a = a.filter(|a| !a.is_nil());
+    let b = b.filter(|b| !b.is_nil());
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => {
+            let numbers = a
+                .as_scalar()
+                .and_then(|a| a.to_float())
+                .zip(b.as_scalar().and_then(|b| b.to_float()));
+            numbers.map_or_else(
+                || a.to_kstr().cmp(&b.to_kstr()),
+                |(a, b)| a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            )
+        }
+    }
+}
+
+impl Filter for SortFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let args = self.args.evaluate(runtime)?;
+        let descending = match args.order.as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(order) => return Err(invalid_input(format!("Unknown sort order: {order}"))),
+        };
+        let mut sorted: Vec<Value> = input.as_array().map_or_else(
+            || vec![input.to_value()],
+            |array| array.values().map(ValueView::to_value).collect(),
+        );
+        let key = |value: &'_ Value| -> Option<Value> {
+            args.property.as_ref().map_or_else(
+                || Some(value.clone()),
+                |path| property(value, path).map(ValueView::to_value),
+            )
+        };
+        sorted.sort_by(|a, b| {
+            let (a, b) = (key(a), key(b));
+            let ordering = compare(
+                a.as_ref().map(|a| a as &dyn ValueView),
+                b.as_ref().map(|b| b as &dyn ValueView),
+            );
+            let missing = a.as_ref().is_none_or(ValueView::is_nil)
+                || b.as_ref().is_none_or(ValueView::is_nil);
+            if descending && !missing {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        Ok(Value::Array(sorted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, globals: &liquid::Object) -> String {
+        let conf = Config {
+            base_url: Some("https://example.com/club/".to_string()),
+            locale: Some("de".to_string()),
+            markdown: Some(Markdown::default()),
+            ..Default::default()
+        };
+        render_with(template, globals, &conf)
+    }
+
+    fn render_with(template: &str, globals: &liquid::Object, conf: &Config) -> String {
+        register(liquid::ParserBuilder::with_stdlib(), Path::new("."), conf)
+            .build()
+            .unwrap()
+            .parse(template)
+            .unwrap()
+            .render(globals)
+            .unwrap()
+    }
+
+    #[test]
+    fn date_filter() {
+        let globals = liquid::object!({ "date": "2024-03-05 08:07:00" });
+        assert_eq!(
+            render("{{ date | date: \"%A, %-d. %B %Y %H:%M\" }}", &globals),
+            "Dienstag, 5. März 2024 08:07"
+        );
+        assert_eq!(
+            render("{{ date | date: \"%a %b %e %F\", \"en-US\" }}", &globals),
+            "Tue Mar  5 2024-03-05"
+        );
+    }
+
+    #[test]
+    fn url_filters() {
+        let globals = liquid::object!({ "root_dir": "../../" });
+        assert_eq!(
+            render("{{ \"/css/site.css\" | relative_url }}", &globals),
+            "../../css/site.css"
+        );
+        assert_eq!(
+            render("{{ \"Posts/index.html\" | absolute_url }}", &globals),
+            "https://example.com/club/Posts/index.html"
+        );
+        assert_eq!(
+            render("{{ \"https://rust-lang.org\" | relative_url }}", &globals),
+            "https://rust-lang.org"
+        );
+        let asset = render("{{ \"Cargo.toml\" | asset_url }}", &globals);
+        assert!(asset.starts_with("../../Cargo.toml?v="));
+        assert_eq!(asset.len(), "../../Cargo.toml?v=".len() + 8);
+        assert_eq!(
+            render(
+                "{{ \"https://cdn.example.com/site.css\" | asset_url }}",
+                &globals
+            ),
+            "https://cdn.example.com/site.css"
+        );
+        let conf = Config {
+            markdown: Some(Markdown::default()),
+            ..Default::default()
+        };
+        assert_eq!(
+            render_with(
+                "{{ \"/Posts/index.html\" | absolute_url }}",
+                &globals,
+                &conf
+            ),
+            "../../Posts/index.html"
+        );
+    }
+
+    #[test]
+    fn text_filters() {
+        let globals = liquid::object!({});
+        assert_eq!(
+            render("{{ \"Dog Training & more!\" | slugify }}", &globals),
+            "dog-training-more"
+        );
+        assert_eq!(
+            render("{{ \"Some *text*\" | markdownify }}", &globals),
+            "<p>Some <em>text</em></p>\n"
+        );
+        assert_eq!(
+            truncate_words_html("<p>One <b>two three</b> four<br> five</p>", 2, "…"),
+            "<p>One <b>two…</b></p>"
+        );
+        assert_eq!(
+            truncate_words_html("<p>One two</p>", 5, "…"),
+            "<p>One two</p>"
+        );
+    }
+
+    #[test]
+    fn where_and_sort_filters() {
+        let globals = liquid::object!({
+            "pages": [
+                { "title": "b", "page": { "date": "2024-02-01", "tags": ["news", "club"] } },
+                { "title": "a", "page": { "date": "2024-03-01", "tags": ["club"] } },
+                { "title": "c", "page": {} },
+            ]
+        });
+        assert_eq!(
+            render(
+                "{% assign news = pages | where: \"page.tags\", \"news\" %}{% for p in news %}{{ p.title }}{% endfor %}",
+                &globals
+            ),
+            "b"
+        );
+        assert_eq!(
+            render(
+                "{% assign dated = pages | where: \"page.date\" %}{{ dated.size }}",
+                &globals
+            ),
+            "2"
+        );
+        assert_eq!(
+            render(
+                "{% assign sorted = pages | sort: \"page.date\", \"desc\" %}{% for p in sorted %}{{ p.title }}{% endfor %}",
+                &globals
+            ),
+            "abc"
+        );
+        assert_eq!(
+            render(
+                "{% assign sorted = pages | sort: \"title\" %}{% for p in sorted %}{{ p.title }}{% endfor %}",
+                &globals
+            ),
+            "abc"
+        );
+    }
+}
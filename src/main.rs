@@ -10,14 +10,28 @@
 #![allow(clippy::similar_names)]
 
 mod config;
+mod data;
+mod feed;
 mod filter;
+mod front_matter;
 mod ftp;
+mod json;
+mod liquid_filters;
 mod macros;
+mod markdown;
+mod publish;
 mod render;
+mod section;
 mod server;
 mod sha1dir;
+mod shortcode;
+mod site;
+mod sitemap;
 mod sync;
+mod taxonomy;
 mod template;
+#[cfg(test)]
+mod test_project;
 
 use crate::config::Config;
 use crate::sync::Synchronizer;
@@ -28,9 +42,18 @@ use flexi_logger::{FileSpec, Logger, LoggerHandle, WriteMode};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// Output directory of `build --drafts` and `serve --drafts`, kept apart
+/// from the `output_dir` that `sync` uploads.
+static DRAFTS_OUTPUT_DIR: &str = ".drafts";
+
+fn drafts_config(path: &Path, mut conf: Config) -> Config {
+    conf.output_dir = Some(path.join(DRAFTS_OUTPUT_DIR).to_string_lossy().into_owned());
+    conf
+}
+
 fn sync(path: &Path, conf: &Config, scratch: bool, overwrite: bool) -> Result<()> {
     if scratch {
-        render::build(path, conf, true)?;
+        render::build(path, conf, true, false)?;
     }
     let mut synchronizer = Synchronizer::new(conf)?;
     if overwrite {
@@ -45,6 +68,16 @@ struct Build {
     /// Generate output from scratch?
     #[clap(short, long)]
     clean: bool,
+    /// Include pages marked as draft?
+    #[clap(short, long)]
+    drafts: bool,
+}
+
+#[derive(Parser)]
+struct Serve {
+    /// Include pages marked as draft?
+    #[clap(short, long)]
+    drafts: bool,
 }
 
 #[derive(Parser)]
@@ -65,8 +98,9 @@ enum Command {
     Build(Build),
     /// Synchronize the website with an sftp or ftp server
     Sync(Sync),
-    /// Start a local http server that allows testing the site
-    Serve,
+    /// Start a local http server that allows testing the site, with drafts
+    /// after building them
+    Serve(Serve),
 }
 
 #[derive(Parser)]
@@ -111,14 +145,29 @@ fn main() -> Result<()> {
         Command::PrintConfig => {
             conf.print();
         }
-        Command::Build(build_args) => render::build(path.as_path(), &conf, build_args.clean)?,
+        Command::Build(build_args) => {
+            let conf = if build_args.drafts {
+                drafts_config(&path, conf)
+            } else {
+                conf
+            };
+            render::build(path.as_path(), &conf, build_args.clean, build_args.drafts)?;
+        }
         Command::Sync(sync_args) => sync(
             path.as_path(),
             &conf,
             sync_args.scratch,
             sync_args.overwrite,
         )?,
-        Command::Serve => server::serve(&conf),
+        Command::Serve(serve_args) => {
+            if serve_args.drafts {
+                let conf = drafts_config(&path, conf);
+                render::build(path.as_path(), &conf, false, true)?;
+                server::serve(&conf);
+            } else {
+                server::serve(&conf);
+            }
+        }
     }
 
     Ok(())
@@ -0,0 +1,642 @@
+use crate::config::{HighlightStyle, Markdown};
+use crate::filter::{MainPages, is_markdown, is_not_hidden};
+use crate::front_matter;
+use crate::publish::Unpublished;
+use crate::shortcode::Shortcodes;
+use anyhow::{Context, Result};
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Parser, Tag, TagEnd, html};
+use serde_derive::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{
+    ClassStyle, ClassedHTMLGenerator, css_for_theme_with_class_style, highlighted_html_for_string,
+};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+static HIGHLIGHT_CSS_FILE: &str = "syntax.css";
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEME_SET: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// HTML of a markdown page together with the table of contents of its headings.
+#[derive(Default)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+    pub broken_links: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TocEntry {
+    pub level: u32,
+    pub text: String,
+    pub anchor: String,
+    pub children: Vec<Self>,
+}
+
+/// All markdown pages of the site with the name of their html file and their
+/// heading anchors. It is used to rewrite and check links between pages.
+#[derive(Default)]
+pub struct LinkIndex {
+    pages: HashMap<PathBuf, IndexedPage>,
+}
+
+struct IndexedPage {
+    html_file: String,
+    anchors: HashSet<String>,
+}
+
+/// The page whose links are rewritten together with the index of all pages.
+pub struct PageLinks<'a> {
+    pub index: &'a LinkIndex,
+    pub source: &'a Path,
+}
+
+impl LinkIndex {
+    /// Indexes the published pages of the project with the anchors of their
+    /// headings after the shortcodes were expanded, like they are rendered.
+    pub fn new(
+        path: &Path,
+        main_pages: &MainPages,
+        settings: &Markdown,
+        unpublished: &Unpublished,
+        shortcodes: &Shortcodes,
+    ) -> Self {
+        let mut pages = HashMap::new();
+        let walker = walkdir::WalkDir::new(path)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(is_not_hidden);
+        for entry in walker
+            .flatten()
+            .filter(|e| is_markdown(e) && !unpublished.contains(e.path()))
+        {
+            let Ok(markdown) = std::fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            // pages that fail to render are reported on their own
+            let Ok((_, body)) = front_matter::split(markdown.as_str()) else {
+                continue;
+            };
+            let Ok(expanded) = shortcodes.expand(body) else {
+                continue;
+            };
+            let mut events: Vec<Event> =
+                Parser::new_ext(&expanded.markdown, settings.options()).collect();
+            let anchors = add_heading_anchors(&mut events)
+                .into_iter()
+                .map(|entry| entry.anchor)
+                .collect();
+            pages.insert(
+                normalize_path(entry.path()),
+                IndexedPage {
+                    html_file: html_file_name(entry.path(), main_pages),
+                    anchors,
+                },
+            );
+        }
+        Self { pages }
+    }
+}
+
+/// Name of the html file a markdown page is rendered to. The main page of a
+/// directory becomes `index.html`, every other page `<name>.html`.
+pub fn html_file_name(page: &Path, main_pages: &MainPages) -> String {
+    if main_pages.contains(page) {
+        "index.html".to_string()
+    } else {
+        format!(
+            "{}.html",
+            page.file_stem().unwrap_or_default().to_string_lossy()
+        )
+    }
+}
+
+pub fn convert_markdown_to_html(
+    markdown: &str,
+    settings: &Markdown,
+    links: Option<&PageLinks>,
+) -> RenderedMarkdown {
+    let mut events: Vec<Event> = Parser::new_ext(markdown, settings.options()).collect();
+    let flat_toc = add_heading_anchors(&mut events);
+    let broken_links = links.map_or_else(Vec::new, |links| {
+        let own_anchors = flat_toc.iter().map(|entry| entry.anchor.as_str()).collect();
+        rewrite_links(&mut events, links, &own_anchors)
+    });
+    let toc = nest_toc(&mut flat_toc.into_iter().peekable(), 0);
+    if let Some(theme) = settings
+        .highlight_theme
+        .as_deref()
+        .and_then(|name| highlight_theme(name).ok())
+    {
+        let style = settings.highlight_style.unwrap_or(HighlightStyle::Inline);
+        events = highlight_code_blocks(events, theme, style);
+    }
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+    RenderedMarkdown {
+        html: html_output,
+        toc,
+        broken_links,
+    }
+}
+
+/// Gives every heading without an explicit id a unique slug id and returns
+/// the flat list of all headings.
+fn add_heading_anchors(events: &mut [Event]) -> Vec<TocEntry> {
+    let mut used_anchors = HashMap::<String, usize>::new();
+    let mut flat_toc = Vec::<TocEntry>::new();
+    let mut idx = 0;
+    while idx < events.len() {
+        if let Event::Start(Tag::Heading { level, id, .. }) = &events[idx] {
+            let level = *level as u32;
+            let explicit_id = id.as_ref().map(ToString::to_string);
+            let text = heading_text(&events[idx + 1..]);
+            let anchor = unique_anchor(
+                explicit_id.unwrap_or_else(|| slugify(&text)),
+                &mut used_anchors,
+            );
+            if let Event::Start(Tag::Heading { id, .. }) = &mut events[idx] {
+                *id = Some(CowStr::from(anchor.clone()));
+            }
+            flat_toc.push(TocEntry {
+                level,
+                text,
+                anchor,
+                children: Vec::new(),
+            });
+        }
+        idx += 1;
+    }
+    flat_toc
+}
+
+fn heading_text(events: &[Event]) -> String {
+    let mut text = String::new();
+    for event in events {
+        match event {
+            Event::End(TagEnd::Heading(_)) => break,
+            Event::Text(t) | Event::Code(t) | Event::InlineMath(t) => text.push_str(t),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Appends the lowest free `-<n>` suffix to an anchor used before. Suffixed
+/// anchors are taken as well, so a later `Intro-1` heading gets `intro-1-1`.
+fn unique_anchor(anchor: String, used_anchors: &mut HashMap<String, usize>) -> String {
+    let Some(count) = used_anchors.get(&anchor).copied() else {
+        used_anchors.insert(anchor.clone(), 1);
+        return anchor;
+    };
+    let mut suffix = count;
+    let candidate = loop {
+        let candidate = format!("{anchor}-{suffix}");
+        suffix += 1;
+        if !used_anchors.contains_key(&candidate) {
+            break candidate;
+        }
+    };
+    used_anchors.insert(anchor, suffix);
+    used_anchors.insert(candidate.clone(), 1);
+    candidate
+}
+
+fn nest_toc(
+    entries: &mut std::iter::Peekable<impl Iterator<Item = TocEntry>>,
+    parent_level: u32,
+) -> Vec<TocEntry> {
+    let mut toc = Vec::new();
+    while let Some(mut entry) = entries.next_if(|e| e.level > parent_level) {
+        entry.children = nest_toc(entries, entry.level);
+        toc.push(entry);
+    }
+    toc
+}
+
+/// Rewrites relative links to markdown files into links to the corresponding
+/// html files and returns a description of every link whose target page or
+/// anchor does not exist.
+fn rewrite_links(
+    events: &mut [Event],
+    links: &PageLinks,
+    own_anchors: &HashSet<&str>,
+) -> Vec<String> {
+    let mut broken_links = Vec::new();
+    for event in events.iter_mut() {
+        let Event::Start(Tag::Link { dest_url, .. }) = event else {
+            continue;
+        };
+        match resolve_link(dest_url, links, own_anchors) {
+            Ok(Some(new_url)) => *dest_url = new_url.into(),
+            Ok(None) => {}
+            Err(reason) => broken_links.push(format!("link '{dest_url}' {reason}")),
+        }
+    }
+    broken_links
+}
+
+fn resolve_link(
+    dest_url: &str,
+    links: &PageLinks,
+    own_anchors: &HashSet<&str>,
+) -> Result<Option<String>, String> {
+    let is_absolute = dest_url.starts_with('/')
+        || dest_url.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        });
+    if is_absolute {
+        return Ok(None);
+    }
+    let (link_path, fragment) = match dest_url.split_once('#') {
+        Some((link_path, fragment)) => (link_path, Some(fragment)),
+        None => (dest_url, None),
+    };
+    if link_path.is_empty() {
+        return match fragment {
+            Some(anchor) if !own_anchors.contains(anchor) => {
+                Err(format!("points to the missing anchor '#{anchor}'"))
+            }
+            _ => Ok(None),
+        };
+    }
+    let is_markdown_link = Path::new(link_path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+    if !is_markdown_link {
+        return Ok(None);
+    }
+    let source_dir = links.source.parent().unwrap_or_else(|| Path::new(""));
+    let target = normalize_path(&source_dir.join(percent_decode(link_path)));
+    let Some(page) = links.index.pages.get(&target) else {
+        return Err("points to a page that does not exist".to_string());
+    };
+    if let Some(anchor) = fragment
+        && !page.anchors.contains(anchor)
+    {
+        return Err(format!("points to the missing anchor '#{anchor}'"));
+    }
+    let mut new_url = link_path
+        .rsplit_once('/')
+        .map_or_else(String::new, |(dir, _)| format!("{dir}/"));
+    new_url.push_str(&page.html_file);
+    if let Some(anchor) = fragment {
+        new_url.push('#');
+        new_url.push_str(anchor);
+    }
+    Ok(Some(new_url))
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%'
+            && let Some(byte) = text
+                .get(idx + 1..idx + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            idx += 3;
+        } else {
+            decoded.push(bytes[idx]);
+            idx += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub fn highlight_theme(name: &str) -> Result<&'static Theme> {
+    THEME_SET.themes.get(name).with_context(|| {
+        format!(
+            "unknown highlight theme '{name}', available themes are: {}",
+            THEME_SET
+                .themes
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+/// Checks the configured highlight theme and writes the stylesheet needed by
+/// code blocks highlighted with CSS classes.
+pub fn prepare_highlighting(settings: &Markdown, output_dir: &Path) -> Result<()> {
+    if let Some(name) = &settings.highlight_theme {
+        let theme = highlight_theme(name)?;
+        if settings.highlight_style == Some(HighlightStyle::Classes) {
+            let css = css_for_theme_with_class_style(theme, ClassStyle::Spaced)?;
+            std::fs::write(output_dir.join(HIGHLIGHT_CSS_FILE), css)
+                .with_context(|| format!("writing '{HIGHLIGHT_CSS_FILE}' failed"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces fenced code blocks that carry a language tag by highlighted HTML.
+fn highlight_code_blocks<'a>(
+    events: Vec<Event<'a>>,
+    theme: &Theme,
+    style: HighlightStyle,
+) -> Vec<Event<'a>> {
+    let mut result = Vec::with_capacity(events.len());
+    let mut code_block: Option<(String, Vec<Event<'a>>)> = None;
+    for event in events {
+        match (&mut code_block, event) {
+            (None, event @ Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(_)))) => {
+                if let Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))) = &event
+                    && !lang.is_empty()
+                {
+                    code_block = Some((lang.to_string(), vec![event]));
+                } else {
+                    result.push(event);
+                }
+            }
+            (Some((lang, block_events)), event @ Event::End(TagEnd::CodeBlock)) => {
+                let code: String = block_events
+                    .iter()
+                    .filter_map(|e| match e {
+                        Event::Text(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect();
+                match highlight(lang, &code, theme, style) {
+                    Ok(html) => result.push(Event::Html(html.into())),
+                    Err(err) => {
+                        log::warn!("highlighting of '{lang}' code failed: {err}");
+                        result.append(block_events);
+                        result.push(event);
+                    }
+                }
+                code_block = None;
+            }
+            (Some((_, block_events)), event) => block_events.push(event),
+            (None, event) => result.push(event),
+        }
+    }
+    result
+}
+
+fn highlight(
+    lang: &str,
+    code: &str,
+    theme: &Theme,
+    style: HighlightStyle,
+) -> Result<String, syntect::Error> {
+    let token = lang
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .next()
+        .unwrap_or_default();
+    let syntax = SYNTAX_SET
+        .find_syntax_by_token(token)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+    match style {
+        HighlightStyle::Inline => highlighted_html_for_string(code, &SYNTAX_SET, syntax, theme),
+        HighlightStyle::Classes => {
+            let mut generator =
+                ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, ClassStyle::Spaced);
+            for line in LinesWithEndings::from(code) {
+                generator.parse_html_for_line_which_includes_newline(line)?;
+            }
+            let lang_class: String = token
+                .chars()
+                .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '+' | '#'))
+                .collect();
+            Ok(format!(
+                "<pre class=\"code\"><code class=\"language-{lang_class}\">{}</code></pre>\n",
+                generator.finalize()
+            ))
+        }
+    }
+}
+
+/// Turns `text` into a lower case, url friendly identifier.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlighted(style: HighlightStyle) -> Markdown {
+        Markdown {
+            highlight_theme: Some("InspiredGitHub".to_string()),
+            highlight_style: Some(style),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn slugify_text() {
+        assert_eq!(slugify("How to feed a dog?"), "how-to-feed-a-dog");
+        assert_eq!(slugify("  Über   uns -- Team_1 "), "über-uns-team-1");
+        assert_eq!(slugify("???"), "section");
+    }
+
+    #[test]
+    fn headings_get_unique_anchors() {
+        let rendered = convert_markdown_to_html(
+            "# Intro\n## Intro\n## `code` *span*",
+            &Markdown::default(),
+            None,
+        );
+        assert!(rendered.html.contains(r#"<h1 id="intro">Intro</h1>"#));
+        assert!(rendered.html.contains(r#"<h2 id="intro-1">Intro</h2>"#));
+        assert!(rendered.html.contains(r#"<h2 id="code-span">"#));
+    }
+
+    #[test]
+    fn suffixed_anchors_do_not_clash() {
+        let rendered = convert_markdown_to_html(
+            "# Intro-1
+# Intro
+# Intro
+# Intro-1",
+            &Markdown::default(),
+            None,
+        );
+        let anchors: Vec<_> = rendered.toc.iter().map(|e| e.anchor.as_str()).collect();
+        assert_eq!(anchors, ["intro-1", "intro", "intro-2", "intro-1-1"]);
+    }
+
+    #[test]
+    fn explicit_heading_ids_are_kept() {
+        let settings = Markdown {
+            heading_attributes: Some(true),
+            ..Default::default()
+        };
+        let rendered = convert_markdown_to_html("# Title {#custom}", &settings, None);
+        assert_eq!(rendered.toc[0].anchor, "custom");
+        assert!(rendered.html.contains(r#"<h1 id="custom">Title</h1>"#));
+    }
+
+    #[test]
+    fn toc_is_nested() {
+        let rendered =
+            convert_markdown_to_html("## A\n# B\n### C\n## D\n# E", &Markdown::default(), None);
+        let summary: Vec<_> = rendered
+            .toc
+            .iter()
+            .map(|e| {
+                (
+                    e.text.as_str(),
+                    e.children
+                        .iter()
+                        .map(|c| c.text.as_str())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("A", vec![]), ("B", vec!["C", "D"]), ("E", vec![])]
+        );
+    }
+
+    #[test]
+    fn code_blocks_are_highlighted() {
+        let code = "```rust\nfn main() {}\n```\n\n```\nplain <text>\n```";
+        let inline = convert_markdown_to_html(code, &highlighted(HighlightStyle::Inline), None);
+        assert!(inline.html.contains("<pre style="));
+        assert!(inline.html.contains("<pre><code>plain &lt;text&gt;"));
+        let classes = convert_markdown_to_html(code, &highlighted(HighlightStyle::Classes), None);
+        assert!(
+            classes
+                .html
+                .contains(r#"<code class="language-rust"><span class="source rust">"#)
+        );
+        assert!(
+            !convert_markdown_to_html(code, &Markdown::default(), None)
+                .html
+                .contains("<span")
+        );
+    }
+
+    #[test]
+    fn unknown_highlight_theme() {
+        assert!(highlight_theme("InspiredGitHub").is_ok());
+        assert!(highlight_theme("no such theme").is_err());
+    }
+
+    #[test]
+    fn links_to_markdown_pages_are_rewritten() {
+        let mut index = LinkIndex::default();
+        for (page, anchors) in [
+            ("/site/Tutorials/Feeding/index.md", vec!["meals"]),
+            ("/site/Posts/Where does it come from/index.md", vec![]),
+        ] {
+            index.pages.insert(
+                PathBuf::from(page),
+                IndexedPage {
+                    html_file: "index.html".to_string(),
+                    anchors: anchors.into_iter().map(str::to_string).collect(),
+                },
+            );
+        }
+        let links = PageLinks {
+            index: &index,
+            source: Path::new("/site/Tutorials/Training/index.md"),
+        };
+        let markdown = "# Intro\n\
+            [a](../Feeding/index.md) [b](../Feeding/index.md#meals) \
+            [c](../../Posts/Where%20does%20it%20come%20from/index.md) [d](#intro) \
+            [e](https://example.com/x.md) [f](image.png)\n\n\
+            [g](../Missing/index.md) [h](../Feeding/index.md#missing) [i](#missing)";
+        let rendered = convert_markdown_to_html(markdown, &Markdown::default(), Some(&links));
+        assert!(
+            rendered
+                .html
+                .contains(r#"<a href="../Feeding/index.html">a</a>"#)
+        );
+        assert!(
+            rendered
+                .html
+                .contains(r#"<a href="../Feeding/index.html#meals">b</a>"#)
+        );
+        assert!(
+            rendered.html.contains(
+                r#"<a href="../../Posts/Where%20does%20it%20come%20from/index.html">c</a>"#
+            )
+        );
+        assert!(rendered.html.contains(r##"<a href="#intro">d</a>"##));
+        assert!(
+            rendered
+                .html
+                .contains(r#"<a href="https://example.com/x.md">e</a>"#)
+        );
+        assert!(rendered.html.contains(r#"<a href="image.png">f</a>"#));
+        assert_eq!(rendered.broken_links.len(), 3);
+    }
+
+    #[test]
+    fn links_to_unpublished_pages_are_broken() {
+        let path = Path::new("examples/simple_blog");
+        let conf = crate::config::Config::load(path).unwrap();
+        let unpublished = Unpublished::collect(
+            path,
+            false,
+            time::OffsetDateTime::now_utc(),
+            &mut Vec::new(),
+        );
+        let parser = liquid::ParserBuilder::with_stdlib().build().unwrap();
+        let shortcodes = Shortcodes::load(&conf, liquid::model::Value::Nil, &parser).unwrap();
+        let index = LinkIndex::new(
+            path,
+            &MainPages::collect(path, &conf),
+            &Markdown::default(),
+            &unpublished,
+            &shortcodes,
+        );
+        let links = PageLinks {
+            index: &index,
+            source: &path.join("Posts/What is Lorem Ipsum/index.md"),
+        };
+        let rendered = convert_markdown_to_html(
+            "[draft](../Summer%20party/index.md) [post](../Where%20does%20it%20come%20from/index.md)",
+            &Markdown::default(),
+            Some(&links),
+        );
+        assert_eq!(
+            rendered.broken_links,
+            ["link '../Summer%20party/index.md' points to a page that does not exist"]
+        );
+    }
+}
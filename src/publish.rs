@@ -0,0 +1,189 @@
+use crate::filter::{MainPages, is_markdown, is_not_hidden};
+use crate::front_matter::{self, FrontMatter};
+use crate::json;
+use crate::markdown;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+use walkdir::WalkDir;
+
+/// Marks a directory and everything beneath it as draft.
+static DRAFT_MARKER: &str = "_draft";
+static PUBLISH_DATE: &str = "publish_date";
+static EXPIRY_DATE: &str = "expiry_date";
+
+/// Markdown pages that must not be part of the generated site (yet).
+#[derive(Debug, Default)]
+pub struct Unpublished {
+    pages: HashSet<PathBuf>,
+    /// Pages whose publish or expiry date cannot be read. They are left out
+    /// of the build, but html generated for them earlier is kept.
+    invalid: HashSet<PathBuf>,
+}
+
+impl Unpublished {
+    /// Collects the pages of the project that are drafts, not yet due or
+    /// expired at `now`. Drafts are kept if `drafts` asks to build them.
+    /// Pages with invalid dates are added to `errors`.
+    pub fn collect(
+        path: &Path,
+        drafts: bool,
+        now: OffsetDateTime,
+        errors: &mut Vec<anyhow::Error>,
+    ) -> Self {
+        let mut pages = HashSet::new();
+        let mut invalid = HashSet::new();
+        let entries = WalkDir::new(path)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(is_not_hidden)
+            .filter_map(Result::ok)
+            .filter(is_markdown);
+        for entry in entries {
+            match unpublished_reason(path, entry.path(), drafts, now) {
+                Ok(Some(reason)) => {
+                    log::info!("Skipping {reason} {}", entry.path().display());
+                    pages.insert(entry.into_path());
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    errors.push(err);
+                    invalid.insert(entry.into_path());
+                }
+            }
+        }
+        Self { pages, invalid }
+    }
+
+    pub fn contains(&self, page: &Path) -> bool {
+        self.pages.contains(page) || self.invalid.contains(page)
+    }
+
+    /// The number of pages left out because of invalid dates.
+    pub fn invalid_count(&self) -> usize {
+        self.invalid.len()
+    }
+
+    /// Deletes html and json files that were generated for pages which are
+    /// unpublished now. Returns whether any html file was removed.
+    pub fn remove_output(&self, path: &Path, output_dir: &Path, main_pages: &MainPages) -> bool {
+        let mut removed = false;
+        for page in &self.pages {
+            let html_file = output_file(path, output_dir, page, main_pages);
+            let url = html_file.strip_prefix(output_dir).unwrap_or(&html_file);
+            let json_file = output_dir.join(json::json_url(&url.to_string_lossy()));
+            if json_file.exists() && fs::remove_file(&json_file).is_ok() {
+                log::info!("Removed json {}", json_file.display());
+            }
+            if html_file.exists() {
+                if let Err(ref err) = fs::remove_file(&html_file) {
+                    println!("{err}");
+                    log::error!("{err}");
+                } else {
+                    log::info!("Removed html {}", html_file.display());
+                    removed = true;
+                }
+            }
+        }
+        removed
+    }
+}
+
+/// The html file a markdown page of the project is rendered to.
+pub fn output_file(path: &Path, output_dir: &Path, page: &Path, main_pages: &MainPages) -> PathBuf {
+    let mut html_file = output_dir.to_path_buf();
+    if let Some(parent_path) = page.parent() {
+        html_file.extend(parent_path.components().skip(path.components().count()));
+    }
+    html_file.push(markdown::html_file_name(page, main_pages));
+    html_file
+}
+
+/// Tells whether a page is a draft, either by its front matter or by a
+/// `_draft` marker in its directory or one above it within the project.
+pub fn is_draft(path: &Path, page: &Path, front_matter: &FrontMatter) -> bool {
+    has_draft_marker(path, page) || front_matter.is_draft()
+}
+
+fn has_draft_marker(path: &Path, page: &Path) -> bool {
+    page.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(path))
+        .any(|dir| dir.join(DRAFT_MARKER).exists())
+}
+
+fn unpublished_reason(
+    path: &Path,
+    page: &Path,
+    drafts: bool,
+    now: OffsetDateTime,
+) -> Result<Option<&'static str>> {
+    if !drafts && has_draft_marker(path, page) {
+        return Ok(Some("draft"));
+    }
+    // invalid front matter is reported when the page gets rendered
+    let Ok(markdown) = fs::read_to_string(page) else {
+        return Ok(None);
+    };
+    let Ok((front_matter, _)) = front_matter::split(&markdown) else {
+        return Ok(None);
+    };
+    if !drafts && is_draft(path, page, &front_matter) {
+        return Ok(Some("draft"));
+    }
+    let dates = |key| {
+        front_matter
+            .date(key, now.offset())
+            .with_context(|| format!("failed to build {}", page.display()))
+    };
+    if dates(PUBLISH_DATE)?.is_some_and(|publish_date| publish_date > now) {
+        return Ok(Some("scheduled page"));
+    }
+    if dates(EXPIRY_DATE)?.is_some_and(|expiry_date| expiry_date <= now) {
+        return Ok(Some("expired page"));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_project::TestProject;
+
+    #[test]
+    fn invalid_dates_are_reported() {
+        let project = TestProject::new("invalid_publish_date");
+        project
+            .write("party.md", "+++\npublish_date = \"soon\"\n+++\n# Party")
+            .write("index.md", "# Home");
+        let path = project.path();
+        let page = path.join("party.md");
+        let mut errors = Vec::new();
+        let unpublished = Unpublished::collect(path, false, OffsetDateTime::now_utc(), &mut errors);
+        assert_eq!(errors.len(), 1);
+        assert!(unpublished.contains(&page));
+        assert!(!unpublished.contains(&path.join("index.md")));
+        assert_eq!(unpublished.invalid_count(), 1);
+    }
+
+    #[test]
+    fn removing_the_home_page_keeps_the_site_index() {
+        let project = TestProject::new("unpublished_home");
+        project
+            .write("index.md", "+++\ndraft = true\n+++\n# Home")
+            .write("PUBLIC/index.html", "<h1>Home</h1>")
+            .write("PUBLIC/_index.json", "{}")
+            .write("PUBLIC/index.json", "{}");
+        let path = project.path();
+        let output_dir = path.join("PUBLIC");
+        let unpublished =
+            Unpublished::collect(path, false, OffsetDateTime::now_utc(), &mut Vec::new());
+        let main_pages = MainPages::collect(path, &crate::config::Config::default());
+        assert!(unpublished.remove_output(path, &output_dir, &main_pages));
+        assert!(!output_dir.join("index.html").exists());
+        assert!(!output_dir.join("_index.json").exists());
+        assert!(output_dir.join("index.json").exists());
+    }
+}
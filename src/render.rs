@@ -1,11 +1,21 @@
-use crate::config::Config;
+use crate::config::{self, Config, FeedFormat, LinkCheck, Markdown, Section};
+use crate::data;
+use crate::feed;
 use crate::filter::{
-    contains_markdown_file, contains_markdown_in_dir, contains_markdown_subdir, is_directory,
-    is_image, is_modified_markdown, is_not_hidden,
+    MainPages, contains_markdown_file, is_directory, is_image, is_markdown, is_modified_markdown,
+    is_not_found_markdown, is_not_hidden,
 };
-use crate::template;
-use anyhow::Result;
-use pulldown_cmark::{Options, Parser, html};
+use crate::front_matter::{self, FrontMatter};
+use crate::json;
+use crate::markdown::{self, LinkIndex, PageLinks, RenderedMarkdown};
+use crate::publish::{self, Unpublished};
+use crate::section::{self, Listing};
+use crate::shortcode::Shortcodes;
+use crate::site::{self, Site, SitePage};
+use crate::sitemap::{self, Sitemap};
+use crate::taxonomy::{self, Taxonomy};
+use crate::template::{self, Templates};
+use anyhow::{Context, Result};
 use rayon::prelude::*;
 use regex::Regex;
 use std::fmt::{self, Debug};
@@ -16,6 +26,8 @@ use std::time::SystemTime;
 use walkdir::{DirEntry, WalkDir};
 
 static BUILD_TIMESTAMP_FILE: &str = "last_build.json";
+static SITE_FINGERPRINT_FILE: &str = "site_pages.sha1";
+static NOT_FOUND_HTML: &str = "404.html";
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum MenuCmd {
@@ -31,12 +43,11 @@ impl fmt::Display for MenuCmd {
     }
 }
 
-pub fn build(path: &Path, conf: &Config, clean: bool) -> Result<()> {
+pub fn build(path: &Path, conf: &Config, clean: bool, drafts: bool) -> Result<()> {
+    let now = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
     log::info!(
         "[{}] Building project `{}`",
-        time::OffsetDateTime::now_local()
-            .unwrap_or_else(|_| time::OffsetDateTime::now_utc())
-            .format(&time::format_description::well_known::Iso8601::DEFAULT)
+        now.format(&time::format_description::well_known::Iso8601::DEFAULT)
             .unwrap_or_else(|_| "??".to_string()),
         conf.title.as_deref().unwrap_or("unknown"),
     );
@@ -44,23 +55,203 @@ pub fn build(path: &Path, conf: &Config, clean: bool) -> Result<()> {
     if clean && output_dir.exists() {
         fs::remove_dir_all(&output_dir)?;
     }
-    let nav_items = prepare_site_structure(path, output_dir.as_path(), conf);
-    let prev_build_timestamp = get_and_set_build_timestamp(output_dir.as_path());
-    let entries: Vec<_> = WalkDir::new(path)
+    DirBuilder::new().recursive(true).create(&output_dir)?;
+    let markdown_settings = conf
+        .markdown
+        .as_ref()
+        .expect("Invalid config: expected markdown settings are missing");
+    let mut errors = Vec::new();
+    let unpublished = Unpublished::collect(path, drafts, now, &mut errors);
+    let main_pages = MainPages::collect(path, conf);
+    let removed_pages = unpublished.remove_output(path, output_dir.as_path(), &main_pages);
+    let templates = Templates::load(path, conf)?;
+    let site = Site::collect(
+        path,
+        conf,
+        &main_pages,
+        &unpublished,
+        now.offset(),
+        &mut errors,
+    );
+    let data = data::load(path)?;
+    let shortcodes = Shortcodes::load(conf, data.clone(), templates.parser())?;
+    let ctx = BuildContext {
+        path,
+        conf,
+        navigation: prepare_site_structure(
+            path,
+            output_dir.as_path(),
+            conf,
+            &main_pages,
+            &unpublished,
+            &mut errors,
+        ),
+        link_index: LinkIndex::new(
+            path,
+            &main_pages,
+            markdown_settings,
+            &unpublished,
+            &shortcodes,
+        ),
+        main_pages: &main_pages,
+        shortcodes,
+        templates,
+        data,
+        config: conf.to_liquid(),
+        site: site.to_liquid(),
+        site_pages: &site.pages,
+    };
+    markdown::prepare_highlighting(markdown_settings, output_dir.as_path())?;
+    let entries = pages_to_render(&ctx, &unpublished, &site, &output_dir, removed_pages);
+    let results: Vec<Result<Vec<String>>> = entries
+        .par_iter()
+        .map(|src| {
+            let mut target_dir = output_dir.clone();
+            if let Some(parent_path) = src.path().parent() {
+                target_dir.extend(parent_path.components().skip(path.components().count()));
+            }
+            build_page(&ctx, src, target_dir.as_path())
+                .with_context(|| format!("failed to build {}", src.path().display()))
+        })
+        .collect();
+    let mut broken_links = Vec::new();
+    let not_found_page = path.join("404.md");
+    let has_not_found_page = not_found_page.is_file() && !unpublished.contains(&not_found_page);
+    let mut page_count = entries.len() + unpublished.invalid_count();
+    if !has_not_found_page && ctx.templates.contains(template::NOT_FOUND) {
+        page_count += 1;
+        if let Err(err) = build_not_found_page(&ctx, output_dir.as_path()) {
+            errors.push(err.context(format!("failed to build {NOT_FOUND_HTML}")));
+        }
+    }
+    page_count += build_site_pages(&ctx, &site, output_dir.as_path(), &mut errors);
+    for result in results {
+        match result {
+            Ok(page_broken_links) => broken_links.extend(page_broken_links),
+            Err(err) => errors.push(err),
+        }
+    }
+    copy_dirs(path, output_dir.as_path(), conf);
+    report_broken_links(&broken_links, markdown_settings)?;
+    report_errors(&errors, page_count)
+}
+
+/// The markdown pages to render: all of them if the site changed since the
+/// last build, otherwise the modified ones.
+fn pages_to_render(
+    ctx: &BuildContext,
+    unpublished: &Unpublished,
+    site: &Site,
+    output_dir: &Path,
+    removed_pages: bool,
+) -> Vec<DirEntry> {
+    let path = ctx.path;
+    let prev_build_timestamp = get_and_set_build_timestamp(output_dir);
+    let mut entries: Vec<_> = WalkDir::new(path)
         .min_depth(1)
         .into_iter()
         .filter_entry(is_not_hidden)
-        .filter(|e| e.is_ok() && is_modified_markdown(e.as_ref().unwrap(), prev_build_timestamp))
+        .filter_map(Result::ok)
+        .filter(|e| is_markdown(e) && !unpublished.contains(e.path()))
+        .filter(|e| !is_section_index(path, ctx.conf, e.path()))
         .collect();
-    entries.par_iter().for_each(|e| {
-        let src = e.as_ref().unwrap();
-        let mut target_dir = output_dir.clone();
-        if let Some(parent_path) = src.path().parent() {
-            target_dir.extend(parent_path.components().skip(path.components().count()));
+    let pages_changed = site_pages_changed(output_dir, site);
+    // pages that were added or removed change the navigation of all pages,
+    // every page may show the data and the front matter of the other pages
+    let site_changed = removed_pages
+        || pages_changed
+        || data::is_modified(path, prev_build_timestamp)
+        || entries
+            .iter()
+            .any(|e| !publish::output_file(path, output_dir, e.path(), ctx.main_pages).exists());
+    if !site_changed {
+        entries.retain(|e| is_modified_markdown(e, prev_build_timestamp));
+    }
+    entries
+}
+
+/// Renders what is generated from all pages of the site: the listings of posts
+/// sections, the taxonomies, the feeds, the json index and the sitemap.
+/// Returns the number of html pages.
+fn build_site_pages(
+    ctx: &BuildContext,
+    site: &Site,
+    output_dir: &Path,
+    errors: &mut Vec<anyhow::Error>,
+) -> usize {
+    let mut page_count = 0;
+    let mut sitemap = Sitemap::default();
+    for page in &site.pages {
+        if !page.front_matter.is_hidden()
+            && !publish::is_draft(ctx.path, &page.source, &page.front_matter)
+        {
+            sitemap.add(page.url.clone(), page.updated);
         }
-        build_page(nav_items.clone(), src, target_dir.as_path(), conf);
-    });
-    copy_dirs(path, output_dir.as_path(), conf);
+    }
+    for section in ctx.conf.sections.iter().flatten() {
+        let listings = section.listings(ctx.path, site);
+        page_count += listings.len();
+        for listing in &listings {
+            sitemap.add(listing.url.clone(), None);
+        }
+        errors.extend(build_listings(ctx, section, listings, output_dir).err());
+    }
+    for name in taxonomy::TAXONOMIES {
+        if ctx.path.join(name).is_dir() {
+            log::warn!("Skipped the {name} pages, the project has a directory of that name");
+            continue;
+        }
+        let taxonomy = Taxonomy::collect(name, site);
+        if !taxonomy.terms.is_empty() {
+            page_count += taxonomy.terms.len() + 1;
+            sitemap.add(taxonomy.url(), None);
+            for term in &taxonomy.terms {
+                sitemap.add(taxonomy::term_url(name, &term.slug), None);
+            }
+        }
+        errors.extend(build_taxonomy(ctx, &taxonomy, output_dir).err());
+    }
+    let feed_formats = ctx.conf.feed_formats();
+    if !feed_formats.is_empty() {
+        errors.extend(build_feeds(ctx, &feed_formats, site, output_dir).err());
+    }
+    if ctx.conf.json_export.unwrap_or(false) {
+        let site_index = json::site_index(site, ctx.conf);
+        errors.extend(write_json_file(&site_index, &output_dir.join(json::INDEX_JSON)).err());
+    }
+    if let Some(sitemap_conf) = &ctx.conf.sitemap {
+        errors.extend(write_sitemap(ctx.conf, sitemap_conf, &sitemap, output_dir).err());
+    }
+    page_count
+}
+
+/// Prints the errors of all pages that could not be built.
+fn report_errors(errors: &[anyhow::Error], page_count: usize) -> Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    for err in errors {
+        println!("{err:#}");
+        log::error!("{err:#}");
+    }
+    anyhow::bail!(
+        "{} of {page_count} page(s) could not be built",
+        errors.len()
+    )
+}
+
+fn report_broken_links(broken_links: &[String], settings: &Markdown) -> Result<()> {
+    let link_check = settings.broken_links.unwrap_or(LinkCheck::Warn);
+    if link_check == LinkCheck::Ignore {
+        return Ok(());
+    }
+    for broken_link in broken_links {
+        println!("{broken_link}");
+        log::warn!("{broken_link}");
+    }
+    if link_check == LinkCheck::Error && !broken_links.is_empty() {
+        anyhow::bail!("Found {} broken link(s)", broken_links.len());
+    }
     Ok(())
 }
 
@@ -85,31 +276,326 @@ fn get_and_set_build_timestamp(outdir: &Path) -> SystemTime {
     last_build
 }
 
-fn build_page(
-    nav_items: Vec<liquid::model::Value>,
-    entry: &DirEntry,
-    target_dir: &Path,
-    conf: &Config,
-) {
-    let page_name = target_dir
-        .file_name()
-        .map_or("None", |name| name.to_str().unwrap_or("None"));
-    let page_content = convert_markdown_to_html(entry.path());
-    let html = if entry.file_name() == "gallery.md" {
-        let images = prepare_gallery(entry, target_dir, conf);
-        apply_gallery_template(
-            &page_content,
-            nav_items,
-            entry.depth(),
-            conf,
-            page_name,
-            images,
-        )
+/// Whether the pages seen by the templates differ from the last build. The
+/// new fingerprint is stored for the next build.
+fn site_pages_changed(outdir: &Path, site: &Site) -> bool {
+    let fingerprint_file = outdir.join(SITE_FINGERPRINT_FILE);
+    let fingerprint = site.fingerprint();
+    let changed = fs::read_to_string(&fingerprint_file).map_or(true, |prev| prev != fingerprint);
+    if changed && let Err(err) = fs::write(&fingerprint_file, fingerprint) {
+        log::error!("Unable to write {}: {err}", fingerprint_file.display());
+    }
+    changed
+}
+
+/// Everything collected once per build that is needed to render the pages.
+struct BuildContext<'a> {
+    path: &'a Path,
+    conf: &'a Config,
+    main_pages: &'a MainPages,
+    navigation: Navigation,
+    link_index: LinkIndex,
+    shortcodes: Shortcodes,
+    templates: Templates,
+    data: liquid::model::Value,
+    config: liquid::model::Value,
+    site: liquid::model::Value,
+    site_pages: &'a [SitePage],
+}
+
+/// A markdown page that is about to be rendered by one of the templates.
+struct PageData<'a> {
+    name: &'a str,
+    /// Path of the html file relative to the output directory.
+    url: String,
+    depth: usize,
+    front_matter: FrontMatter,
+    content: RenderedMarkdown,
+}
+
+fn build_page(ctx: &BuildContext, entry: &DirEntry, target_dir: &Path) -> Result<Vec<String>> {
+    let conf = ctx.conf;
+    let (front_matter, content) = load_markdown(entry.path(), ctx)?;
+    let is_index = ctx.main_pages.contains(entry.path());
+    let page = PageData {
+        name: if is_index {
+            target_dir.file_name()
+        } else {
+            entry.path().file_stem()
+        }
+        .map_or("None", |name| name.to_str().unwrap_or("None")),
+        url: site::page_url(ctx.path, entry.path(), ctx.main_pages),
+        depth: entry.depth(),
+        front_matter,
+        content,
+    };
+    let is_gallery = entry.file_name() == "gallery.md";
+    let layout = page_layout(ctx, entry.path(), &page.front_matter, is_gallery);
+    let html = if is_gallery {
+        let images = prepare_gallery(entry, target_dir, conf)?;
+        apply_gallery_template(&page, ctx, &layout, images)?
     } else {
-        apply_page_template(&page_content, nav_items, entry.depth(), conf, page_name)
+        apply_page_template(&page, ctx, &layout)?
+    };
+    write_html_file(ctx, &html, target_dir, entry)?;
+    write_page_json(ctx, entry.path(), &page.content.html, target_dir)?;
+    if is_index {
+        copy_images(entry.path().parent().unwrap(), target_dir)?;
+    }
+    Ok(page
+        .content
+        .broken_links
+        .into_iter()
+        .map(|broken_link| format!("{}: {broken_link}", entry.path().display()))
+        .collect())
+}
+
+/// The main page of a posts section is part of its first listing page.
+fn is_section_index(path: &Path, conf: &Config, page: &Path) -> bool {
+    conf.sections
+        .iter()
+        .flatten()
+        .any(|section| section.index_markdown(path).as_deref() == Some(page))
+}
+
+/// Renders the listing pages of a posts section. The front matter and content
+/// of the main page of the section directory are shown on every page.
+fn build_listings(
+    ctx: &BuildContext,
+    section: &Section,
+    listings: Vec<Listing>,
+    output_dir: &Path,
+) -> Result<()> {
+    let section_dir = section.dir(ctx.path);
+    let target_dir = output_dir.join(&section.dir);
+    let (front_matter, content) = match section.index_markdown(ctx.path) {
+        Some(index) => {
+            copy_images(&section_dir, &target_dir)?;
+            let (front_matter, content) = load_markdown(&index, ctx)
+                .with_context(|| format!("failed to build {}", index.display()))?;
+            write_page_json(ctx, &index, &content.html, &target_dir)?;
+            (front_matter, content)
+        }
+        None => (FrontMatter::default(), RenderedMarkdown::default()),
     };
-    write_html_file(&html, target_dir, entry);
-    copy_images(entry.path().parent().unwrap(), target_dir);
+    let layout = front_matter
+        .get_str("layout")
+        .or_else(|| section.layout.clone())
+        .unwrap_or_else(|| template::SECTION.to_owned());
+    // listings of a shrinking section must not leave stale pages behind
+    let page_dir = target_dir.join(section::PAGE_DIR);
+    if page_dir.exists() {
+        fs::remove_dir_all(&page_dir)?;
+    }
+    let name = section_dir
+        .file_name()
+        .map_or_else(String::new, |name| nav_name(name, ctx.conf));
+    let mut page = PageData {
+        name: &name,
+        url: String::new(),
+        depth: 0,
+        front_matter,
+        content,
+    };
+    for listing in listings {
+        page.url = listing.url;
+        page.depth = listing.depth + 1;
+        let mut root_dir = String::from("./");
+        for _ in 1..page.depth {
+            root_dir.push_str("../");
+        }
+        let mut context = page_context(&page, ctx, &root_dir);
+        context.insert("paginator".into(), listing.paginator);
+        let html = ctx
+            .templates
+            .render(&layout, &mut context)
+            .with_context(|| format!("failed to build {}", page.url))?;
+        let file_path = output_dir.join(&page.url);
+        if let Some(dir) = file_path.parent() {
+            DirBuilder::new().recursive(true).create(dir)?;
+        }
+        fs::write(&file_path, html)
+            .with_context(|| format!("Could not write html file {}", file_path.display()))?;
+        log::info!("Rendered html {}", file_path.display());
+    }
+    Ok(())
+}
+
+/// Renders the index of a taxonomy and the listing of each of its terms. The
+/// output directory of the taxonomy is rebuilt from scratch so that unused
+/// terms disappear.
+fn build_taxonomy(ctx: &BuildContext, taxonomy: &Taxonomy, output_dir: &Path) -> Result<()> {
+    let taxonomy_dir = output_dir.join(taxonomy.name);
+    if taxonomy_dir.exists() {
+        fs::remove_dir_all(&taxonomy_dir)?;
+    }
+    if taxonomy.terms.is_empty() {
+        return Ok(());
+    }
+    let taxonomy_value = taxonomy.to_liquid();
+    let render =
+        |page: &PageData, layout: &str, term: Option<liquid::model::Value>| -> Result<()> {
+            let mut root_dir = String::from("./");
+            for _ in 1..page.depth {
+                root_dir.push_str("../");
+            }
+            let mut context = page_context(page, ctx, &root_dir);
+            context.insert("taxonomy".into(), taxonomy_value.clone());
+            if let Some(term) = term {
+                context.insert("term".into(), term);
+            }
+            let html = ctx
+                .templates
+                .render(layout, &mut context)
+                .with_context(|| format!("failed to build {}", page.url))?;
+            let file_path = output_dir.join(&page.url);
+            if let Some(dir) = file_path.parent() {
+                DirBuilder::new().recursive(true).create(dir)?;
+            }
+            fs::write(&file_path, html)
+                .with_context(|| format!("Could not write html file {}", file_path.display()))?;
+            log::info!("Rendered html {}", file_path.display());
+            Ok(())
+        };
+    let index = PageData {
+        name: taxonomy.name,
+        url: taxonomy.url(),
+        depth: 2,
+        front_matter: FrontMatter::default(),
+        content: RenderedMarkdown::default(),
+    };
+    render(&index, template::TAXONOMY, None)?;
+    for term in &taxonomy.terms {
+        let page = PageData {
+            name: &term.name,
+            url: taxonomy::term_url(taxonomy.name, &term.slug),
+            depth: 3,
+            front_matter: FrontMatter::default(),
+            content: RenderedMarkdown::default(),
+        };
+        render(
+            &page,
+            template::TAXONOMY_TERM,
+            Some(term.to_liquid(taxonomy.name)),
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the feeds of the whole site or of each of the configured
+/// directories in the given formats.
+fn build_feeds(
+    ctx: &BuildContext,
+    formats: &[FeedFormat],
+    site: &Site,
+    output_dir: &Path,
+) -> Result<()> {
+    let Some(base_url) = ctx.conf.base_url.as_deref() else {
+        if ctx.conf.feed.is_none() {
+            log::warn!("Skipped the JSON Feed, it needs the base_url of the site in config.toml");
+            return Ok(());
+        }
+        anyhow::bail!("feeds need the base_url of the site in config.toml");
+    };
+    let default_feed = config::Feed::default();
+    let feed_conf = ctx.conf.feed.as_ref().unwrap_or(&default_feed);
+    let limit = feed_conf.limit.unwrap_or(config::DEFAULT_FEED_LIMIT);
+    let full_content = feed_conf.full_content.unwrap_or(false);
+    let title = ctx.conf.title.clone().unwrap_or_default();
+    let dirs: Vec<Option<&str>> = feed_conf.sections.as_ref().map_or_else(
+        || vec![None],
+        |dirs| dirs.iter().map(|dir| Some(dir.trim_matches('/'))).collect(),
+    );
+    for dir in dirs {
+        let source_dir = dir.map(|dir| ctx.path.join(dir));
+        let mut entries = Vec::new();
+        for page in feed::newest_pages(&site.pages, source_dir.as_deref(), limit) {
+            let (_, content) = load_markdown(&page.source, ctx)
+                .with_context(|| format!("failed to build {}", page.source.display()))?;
+            entries.push(feed::Entry {
+                page,
+                html: content.html,
+            });
+        }
+        let feed = feed::Feed {
+            title: source_dir.as_deref().and_then(Path::file_name).map_or_else(
+                || title.clone(),
+                |name| format!("{title} - {}", nav_name(name, ctx.conf)),
+            ),
+            dir: dir.map_or_else(String::new, |dir| format!("{dir}/")),
+            entries,
+        };
+        for &format in formats {
+            let file_path = output_dir.join(feed.url(format));
+            if let Some(dir) = file_path.parent() {
+                DirBuilder::new().recursive(true).create(dir)?;
+            }
+            fs::write(&file_path, feed.render(format, base_url, full_content))
+                .with_context(|| format!("Could not write feed {}", file_path.display()))?;
+            log::info!("Rendered feed {}", file_path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Writes the `sitemap.xml` and unless switched off the `robots.txt`.
+fn write_sitemap(
+    conf: &Config,
+    sitemap_conf: &config::Sitemap,
+    sitemap: &Sitemap,
+    output_dir: &Path,
+) -> Result<()> {
+    let Some(base_url) = conf.base_url.as_deref() else {
+        anyhow::bail!("the sitemap needs the base_url of the site in config.toml");
+    };
+    let file_path = output_dir.join(sitemap::SITEMAP_XML);
+    fs::write(&file_path, sitemap.to_xml(base_url))
+        .with_context(|| format!("Could not write sitemap {}", file_path.display()))?;
+    log::info!("Rendered sitemap {}", file_path.display());
+    if sitemap_conf.robots_txt.unwrap_or(true) {
+        let file_path = output_dir.join(sitemap::ROBOTS_TXT);
+        fs::write(&file_path, sitemap::robots_txt(sitemap_conf, base_url))
+            .with_context(|| format!("Could not write {}", file_path.display()))?;
+        log::info!("Rendered {}", file_path.display());
+    }
+    Ok(())
+}
+
+/// Renders the `404` template to `404.html` for projects without a `404.md`.
+fn build_not_found_page(ctx: &BuildContext, output_dir: &Path) -> Result<()> {
+    let page = PageData {
+        name: template::NOT_FOUND,
+        url: NOT_FOUND_HTML.to_owned(),
+        depth: 1,
+        front_matter: FrontMatter::default(),
+        content: RenderedMarkdown::default(),
+    };
+    let html = apply_page_template(&page, ctx, template::NOT_FOUND)?;
+    let file_path = output_dir.join(NOT_FOUND_HTML);
+    fs::write(&file_path, html)
+        .with_context(|| format!("Could not write html file {}", file_path.display()))?;
+    log::info!("Rendered html {}", file_path.display());
+    Ok(())
+}
+
+/// The `layout` of the front matter wins over the `_layout` of the directory.
+/// Galleries only use another layout than `gallery` if their front matter asks for it.
+fn page_layout(
+    ctx: &BuildContext,
+    page: &Path,
+    front_matter: &FrontMatter,
+    is_gallery: bool,
+) -> String {
+    if let Some(layout) = front_matter.get_str("layout") {
+        return layout;
+    }
+    if is_gallery {
+        return template::GALLERY.to_owned();
+    }
+    if is_not_found_markdown(ctx.path, page) && ctx.templates.contains(template::NOT_FOUND) {
+        return template::NOT_FOUND.to_owned();
+    }
+    template::directory_layout(ctx.path, page).unwrap_or_else(|| template::PAGE.to_owned())
 }
 
 fn is_file_modified(src: &Path, trg: &Path) -> bool {
@@ -151,7 +637,7 @@ fn copy_dirs(path: &Path, target_path: &Path, conf: &Config) {
     }
 }
 
-fn copy_images(source: &Path, target: &Path) {
+fn copy_images(source: &Path, target: &Path) -> Result<()> {
     let walker = WalkDir::new(source)
         .min_depth(1)
         .max_depth(1)
@@ -165,14 +651,16 @@ fn copy_images(source: &Path, target: &Path) {
         target_file.push(entry.path().file_name().unwrap());
         if !target_file.exists() || is_file_modified(entry.path(), &target_file) {
             fs::copy(entry.path(), target_file.as_path())
-                .unwrap_or_else(|_| panic!("Error during copy of {:?}", entry.path().display()));
+                .with_context(|| format!("Error during copy of {}", entry.path().display()))?;
         }
     }
+    Ok(())
 }
 
-fn remove_number_prefix<'a>(name: &'a str, conf: &Config) -> &'a str {
-    static NUM_PREFIX: std::sync::LazyLock<Regex> =
-        std::sync::LazyLock::new(|| Regex::new("^[0-9]+_.+$").unwrap());
+pub fn remove_number_prefix<'a>(name: &'a str, conf: &Config) -> &'a str {
+    static NUM_PREFIX: std::sync::LazyLock<Regex> = std::sync::LazyLock::new(|| {
+        Regex::new("^[0-9]+_.+$|^[0-9]{4}-[0-9]{2}-[0-9]{2}_.+$").unwrap()
+    });
     if (conf.remove_numbered_prefix.is_none() || conf.remove_numbered_prefix.unwrap_or(true))
         && NUM_PREFIX.is_match(name)
     {
@@ -184,69 +672,276 @@ fn remove_number_prefix<'a>(name: &'a str, conf: &Config) -> &'a str {
     }
 }
 
+/// A directory or an additional page of a directory as shown in the navigation.
+struct NavNode {
+    name: String,
+    url: String,
+    depth: usize,
+    children: Vec<Self>,
+}
+
+/// The navigation of the site, once as tree and once as flat stream of `MenuCmd`s.
+struct Navigation {
+    tree: Vec<NavNode>,
+    items: Vec<liquid::model::Value>,
+    /// The main page of the site, it is not part of the tree.
+    home: Option<NavNode>,
+}
+
+impl Navigation {
+    /// The `nav_tree` of the page with the html file `page_url`. The entry of
+    /// the page is marked `is_active`, the directories above it `is_ancestor`.
+    fn tree_for(&self, page_url: &str) -> liquid::model::Value {
+        liquid::model::Value::Array(nav_tree_values(&self.tree, Path::new(page_url)).0)
+    }
+
+    /// The main page followed by the nav nodes leading to `page_url`.
+    fn breadcrumbs(&self, page_url: &str) -> liquid::model::Value {
+        let trail = nav_trail(&self.tree, Path::new(page_url)).unwrap_or_default();
+        let crumbs = self
+            .home
+            .iter()
+            .chain(trail)
+            .map(NavNode::to_link)
+            .collect();
+        liquid::model::Value::Array(crumbs)
+    }
+
+    /// The pages before and after `page_url` in the order of the navigation.
+    fn neighbours(&self, page_url: &str) -> (liquid::model::Value, liquid::model::Value) {
+        let mut pages = Vec::new();
+        pages.extend(&self.home);
+        collect_nav_pages(&self.tree, &mut pages);
+        let page_url = Path::new(page_url);
+        let Some(pos) = pages
+            .iter()
+            .position(|node| Path::new(&node.url) == page_url)
+        else {
+            return (liquid::model::Value::Nil, liquid::model::Value::Nil);
+        };
+        let link =
+            |node: Option<&&NavNode>| node.map_or(liquid::model::Value::Nil, |n| n.to_link());
+        (
+            link(pos.checked_sub(1).and_then(|prev| pages.get(prev))),
+            link(pages.get(pos + 1)),
+        )
+    }
+}
+
+impl NavNode {
+    fn to_link(&self) -> liquid::model::Value {
+        liquid::model::Value::Object(liquid::object!({
+            "name": self.name.clone(),
+            "url": self.url.clone(),
+        }))
+    }
+}
+
+/// The nav nodes from the top level down to the node of `page_url`.
+fn nav_trail<'a>(nav_nodes: &'a [NavNode], page_url: &Path) -> Option<Vec<&'a NavNode>> {
+    nav_nodes.iter().find_map(|node| {
+        if !node.url.is_empty() && Path::new(&node.url) == page_url {
+            return Some(vec![node]);
+        }
+        let mut trail = nav_trail(&node.children, page_url)?;
+        trail.insert(0, node);
+        Some(trail)
+    })
+}
+
+/// All nav nodes with a page of their own in the order of the navigation.
+fn collect_nav_pages<'a>(nav_nodes: &'a [NavNode], pages: &mut Vec<&'a NavNode>) {
+    for node in nav_nodes {
+        if !node.url.is_empty() {
+            pages.push(node);
+        }
+        collect_nav_pages(&node.children, pages);
+    }
+}
+
+/// Converts nav nodes to liquid and tells whether one of them is `page_url`
+/// or contains it.
+fn nav_tree_values(nav_nodes: &[NavNode], page_url: &Path) -> (Vec<liquid::model::Value>, bool) {
+    let mut contains_page = false;
+    let values = nav_nodes
+        .iter()
+        .map(|node| {
+            let (children, is_ancestor) = nav_tree_values(&node.children, page_url);
+            let is_active = !node.url.is_empty() && Path::new(&node.url) == page_url;
+            contains_page |= is_active || is_ancestor;
+            liquid::model::Value::Object(liquid::object!({
+                "name": node.name.clone(),
+                "url": node.url.clone(),
+                "is_active": is_active,
+                "is_ancestor": is_ancestor,
+                "children": children,
+            }))
+        })
+        .collect();
+    (values, contains_page)
+}
+
 fn prepare_site_structure(
     path: &Path,
     target_path: &Path,
     conf: &Config,
-) -> Vec<liquid::model::Value> {
+    main_pages: &MainPages,
+    unpublished: &Unpublished,
+    errors: &mut Vec<anyhow::Error>,
+) -> Navigation {
+    let nav_tree = collect_nav_nodes(
+        path,
+        path,
+        target_path,
+        conf,
+        main_pages,
+        unpublished,
+        errors,
+    );
     let mut nav_entries = Vec::<liquid::model::Value>::new();
-    let walker = WalkDir::new(path)
+    let mut prev_depth = 1;
+    flatten_nav_nodes(&nav_tree, &mut nav_entries, &mut prev_depth);
+    if prev_depth > 1 {
+        let nav_entry = liquid::object!({
+            "name": String::new(),
+            "url" : String::new(),
+            "menu_cmd" : MenuCmd::CloseLevel.to_string(),
+            "level_depth" : prev_depth - 1,
+        });
+        nav_entries.push(liquid::model::Value::Object(nav_entry));
+    }
+    let home = main_pages
+        .of(path)
+        .filter(|index| !unpublished.contains(index))
+        .map(|_| NavNode {
+            name: conf.title.clone().unwrap_or_else(|| "Home".to_string()),
+            url: "index.html".to_string(),
+            depth: 0,
+            children: Vec::new(),
+        });
+    Navigation {
+        tree: nav_tree,
+        items: nav_entries,
+        home,
+    }
+}
+
+fn collect_nav_nodes(
+    path: &Path,
+    dir: &Path,
+    target_path: &Path,
+    conf: &Config,
+    main_pages: &MainPages,
+    unpublished: &Unpublished,
+    errors: &mut Vec<anyhow::Error>,
+) -> Vec<NavNode> {
+    let mut nav_nodes = Vec::new();
+    let walker = WalkDir::new(dir)
         .min_depth(1)
+        .max_depth(1)
         .sort_by(|a, b| a.file_name().cmp(b.file_name()))
         .into_iter();
-    let mut prev_depth = 1;
-    for entry in
-        walker.filter_entry(|e| is_not_hidden(e) && is_directory(e) && contains_markdown_file(e))
-    {
-        let entry = entry.expect("Reading directory entry failed");
-        let name = String::from(remove_number_prefix(
-            entry
-                .file_name()
-                .to_str()
-                .expect("Failed to read navigation entries"),
-            conf,
-        ));
+    for entry in walker.filter_entry(is_not_hidden) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                errors.push(anyhow::Error::from(err).context("failed to read navigation entries"));
+                continue;
+            }
+        };
         let mut url = PathBuf::new();
         url.extend(entry.path().components().skip(path.components().count()));
-        let target_dir = target_path.join(url.as_path());
-        if let Err(ref err) = DirBuilder::new().recursive(true).create(target_dir) {
-            println!("{err}");
-            log::error!("{err}");
+        let depth = url.components().count();
+        if is_directory(&entry) && contains_markdown_file(&entry) {
+            let children = collect_nav_nodes(
+                path,
+                entry.path(),
+                target_path,
+                conf,
+                main_pages,
+                unpublished,
+                errors,
+            );
+            let has_index = main_pages
+                .of(entry.path())
+                .is_some_and(|f| !unpublished.contains(f))
+                || conf
+                    .sections
+                    .iter()
+                    .flatten()
+                    .any(|section| section.dir(path) == entry.path());
+            if !has_index && children.is_empty() {
+                continue;
+            }
+            let target_dir = target_path.join(url.as_path());
+            if let Err(err) = DirBuilder::new().recursive(true).create(&target_dir) {
+                errors.push(
+                    anyhow::Error::from(err)
+                        .context(format!("failed to create {}", target_dir.display())),
+                );
+            }
+            url.push("index.html");
+            nav_nodes.push(NavNode {
+                name: nav_name(entry.file_name(), conf),
+                url: if has_index {
+                    url.to_string_lossy().into_owned()
+                } else {
+                    String::new()
+                },
+                depth,
+                children,
+            });
+        } else if is_markdown(&entry)
+            && !main_pages.contains(entry.path())
+            && !is_not_found_markdown(path, entry.path())
+            && !unpublished.contains(entry.path())
+        {
+            url.set_file_name(markdown::html_file_name(entry.path(), main_pages));
+            nav_nodes.push(NavNode {
+                name: nav_name(entry.path().file_stem().unwrap_or_default(), conf),
+                url: url.to_string_lossy().into_owned(),
+                depth,
+                children: Vec::new(),
+            });
         }
-        url.push("index.html");
-        let (menu_cmd, level_depth) =
-            match (contains_markdown_subdir(&entry), prev_depth > entry.depth()) {
-                (true, true) => (MenuCmd::CloseOpenLevel, prev_depth - entry.depth() - 1),
-                (true, false) => (MenuCmd::OpenLevel, 0),
-                (false, true) => (MenuCmd::CloseLevel, prev_depth - entry.depth() - 1),
-                _ => (MenuCmd::None, 0),
-            };
-        let nav_entry = liquid::object!({
-            "name": name,
-            "url" : if contains_markdown_in_dir(&entry) { url.as_os_str().to_str().unwrap().to_owned() } else { String::new() },
-            "menu_cmd" : menu_cmd.to_string().clone(),
-            "level_depth" : level_depth,
-        });
-        nav_entries.push(liquid::model::Value::Object(nav_entry));
-        prev_depth = entry.depth();
     }
-    if prev_depth > 1 {
+    nav_nodes
+}
+
+fn nav_name(file_name: &std::ffi::OsStr, conf: &Config) -> String {
+    String::from(remove_number_prefix(&file_name.to_string_lossy(), conf))
+}
+
+/// Turns the navigation tree into the flat stream of `MenuCmd` entries.
+fn flatten_nav_nodes(
+    nav_nodes: &[NavNode],
+    nav_entries: &mut Vec<liquid::model::Value>,
+    prev_depth: &mut usize,
+) {
+    for node in nav_nodes {
+        let (menu_cmd, level_depth) = match (!node.children.is_empty(), *prev_depth > node.depth) {
+            (true, true) => (MenuCmd::CloseOpenLevel, *prev_depth - node.depth - 1),
+            (true, false) => (MenuCmd::OpenLevel, 0),
+            (false, true) => (MenuCmd::CloseLevel, *prev_depth - node.depth - 1),
+            _ => (MenuCmd::None, 0),
+        };
         let nav_entry = liquid::object!({
-            "name": String::new(),
-            "url" : String::new(),
-            "menu_cmd" : MenuCmd::CloseLevel.to_string(),
-            "level_depth" : prev_depth - 1,
+            "name": node.name.clone(),
+            "url" : node.url.clone(),
+            "menu_cmd" : menu_cmd.to_string(),
+            "level_depth" : level_depth,
         });
         nav_entries.push(liquid::model::Value::Object(nav_entry));
+        *prev_depth = node.depth;
+        flatten_nav_nodes(&node.children, nav_entries, prev_depth);
     }
-    nav_entries
 }
 
 fn prepare_gallery(
     source_entry: &DirEntry,
     target_path: &Path,
     conf: &Config,
-) -> Vec<liquid::model::Value> {
+) -> Result<Vec<liquid::model::Value>> {
     let gallery_settings = conf
         .gallery
         .as_ref()
@@ -280,12 +975,12 @@ fn prepare_gallery(
     .collect::<Vec<_>>();
     for entry in entries {
         let entry = entry.unwrap();
-        let mut img = image::open(entry.path()).unwrap_or_else(|e| {
-            panic!(
-                "Resize of '{}' failed: The gallery folder should only contain images!\n {e}",
+        let mut img = image::open(entry.path()).with_context(|| {
+            format!(
+                "Resize of '{}' failed: The gallery folder should only contain images!",
                 entry.path().display()
             )
-        });
+        })?;
 
         let mut image_path = PathBuf::from(&target_dir);
         let mut rel_image_path = PathBuf::from(img_dir.as_str());
@@ -294,14 +989,14 @@ fn prepare_gallery(
         rel_image_path.push(entry.file_name());
         rel_image_path.set_extension(img_format.extension());
         if !image_path.exists() {
-            let _ = &mut File::create(&image_path).unwrap();
+            File::create(&image_path)?;
             img = img.resize(
                 gallery_settings.img_width,
                 gallery_settings.img_height,
                 image::imageops::FilterType::Nearest,
             );
             img.save_with_format(&image_path, (*img_format).into())
-                .unwrap_or_else(|_| panic!("Saving image '{}' failed", image_path.display()));
+                .with_context(|| format!("Saving image '{}' failed", image_path.display()))?;
         }
 
         let mut thumb_path = PathBuf::from(&target_dir);
@@ -318,14 +1013,14 @@ fn prepare_gallery(
         thumb_path.push(thumb_file_name.clone());
         rel_thumb_path.push(thumb_file_name);
         if !thumb_path.exists() {
-            let _ = &mut File::create(&thumb_path).unwrap();
+            File::create(&thumb_path)?;
             img = img.resize(
                 gallery_settings.thumb_width,
                 gallery_settings.thumb_height,
                 image::imageops::FilterType::Nearest,
             );
             img.save_with_format(&thumb_path, (*img_format).into())
-                .unwrap_or_else(|_| panic!("Saving thumb image '{}' failed", thumb_path.display()));
+                .with_context(|| format!("Saving thumb image '{}' failed", thumb_path.display()))?;
         }
 
         let image_entry = liquid::object!({
@@ -334,122 +1029,222 @@ fn prepare_gallery(
         });
         images.push(liquid::model::Value::Object(image_entry));
     }
-    images
+    Ok(images)
 }
 
 fn apply_gallery_template(
-    content: &str,
-    nav_items: Vec<liquid::model::Value>,
-    depth: usize,
-    conf: &Config,
-    page_name: &str,
+    page: &PageData,
+    ctx: &BuildContext,
+    layout: &str,
     images: Vec<liquid::model::Value>,
-) -> String {
-    let template = liquid::ParserBuilder::with_stdlib()
-        .build()
-        .unwrap()
-        .parse(template::load_gallery(conf).as_str())
-        .expect("Gallery template could not be parsed!");
+) -> Result<String> {
     let mut root_dir = String::new();
-    for _ in 1..depth {
+    for _ in 1..page.depth {
         root_dir.push_str("../");
     }
-    let mut context = liquid::model::Object::new();
-    context.insert("root_dir".into(), liquid::model::Value::scalar(root_dir));
-    context.insert(
-        "title".into(),
-        liquid::model::Value::scalar(if conf.title.is_some() {
-            conf.title.as_ref().unwrap().clone()
-        } else {
-            "None".to_string()
-        }),
-    );
-    context.insert("nav_items".into(), liquid::model::Value::Array(nav_items));
-    context.insert(
-        "content".into(),
-        liquid::model::Value::scalar(content.to_owned()),
-    );
+    let mut context = page_context(page, ctx, &root_dir);
     context.insert("images".into(), liquid::model::Value::Array(images));
-    context.insert(
-        "page_name".into(),
-        liquid::model::Value::scalar(page_name.to_owned()),
-    );
-    match template.render(&context) {
-        Ok(output) => output,
-        Err(error) => panic!("Could not render Page template: {error}"),
-    }
+    ctx.templates.render(layout, &mut context)
 }
 
-fn apply_page_template(
-    content: &str,
-    nav_items: Vec<liquid::model::Value>,
-    depth: usize,
-    conf: &Config,
-    page_name: &str,
-) -> String {
-    let template = liquid::ParserBuilder::with_stdlib()
-        .build()
-        .unwrap()
-        .parse(template::load_page(conf).as_str())
-        .expect("Page template could not be parsed!");
+fn apply_page_template(page: &PageData, ctx: &BuildContext, layout: &str) -> Result<String> {
     let mut root_dir = String::from("./");
-    for _ in 1..depth {
+    for _ in 1..page.depth {
         root_dir.push_str("../");
     }
-    let context = liquid::object!({
-       "root_dir" : root_dir,
-       "title" : if conf.title.is_some() {
-            conf.title.as_ref().unwrap().clone()
-        } else {
-            "None".to_string()
-        },
-        "nav_items" : liquid::model::Value::Array(nav_items),
-        "content" : content.to_owned(),
-        "page_name" : page_name.to_owned()
-    });
-    match template.render(&context) {
-        Ok(output) => output,
-        Err(error) => panic!("Could not render Page template: {error}"),
+    if page.url == NOT_FOUND_HTML {
+        root_dir = not_found_root_dir(ctx.conf);
     }
+    let mut context = page_context(page, ctx, &root_dir);
+    ctx.templates.render(layout, &mut context)
 }
 
-fn convert_markdown_to_html(entry: &Path) -> String {
-    let mut markdown = String::new();
-    let mut html_output = String::new();
-    match File::open(entry).and_then(|mut f| f.read_to_string(&mut markdown)) {
-        Err(error) => panic!("failed to open {}: {error}", entry.display()),
-        Ok(_) => html::push_html(
-            &mut html_output,
-            Parser::new_ext(markdown.as_str(), Options::empty()),
-        ),
+/// The 404 page is served for missing pages at any depth, so relative links
+/// would break. Its links start at the path of the `base_url` instead.
+fn not_found_root_dir(conf: &Config) -> String {
+    let path = conf
+        .base_url
+        .as_deref()
+        .and_then(|base_url| base_url.split_once("://"))
+        .and_then(|(_, rest)| rest.split_once('/'))
+        .map_or("", |(_, path)| path.trim_matches('/'));
+    if path.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{path}/")
     }
-    html_output
 }
 
-fn write_html_file(html: &str, target_dir: &Path, entry: &DirEntry) {
-    #[allow(clippy::option_if_let_else)]
-    let html_file = match entry.file_name().to_str() {
-        Some(fname) => {
-            let mut tmp_path = PathBuf::from(fname);
-            tmp_path.set_file_name("index.html");
-            tmp_path
-        }
-        None => panic!("Could not write html file {}", entry.path().display()),
+fn page_context(page: &PageData, ctx: &BuildContext, root_dir: &str) -> liquid::model::Object {
+    let (prev_page, next_page) = ctx.navigation.neighbours(&page.url);
+    liquid::object!({
+        "root_dir" : root_dir.to_owned(),
+        "title" : ctx.conf.title.clone().unwrap_or_else(|| "None".to_string()),
+        "nav_items" : liquid::model::Value::Array(ctx.navigation.items.clone()),
+        "nav_tree" : ctx.navigation.tree_for(&page.url),
+        "breadcrumbs" : ctx.navigation.breadcrumbs(&page.url),
+        "prev_page" : prev_page,
+        "next_page" : next_page,
+        "data" : ctx.data.clone(),
+        "config" : ctx.config.clone(),
+        "site" : ctx.site.clone(),
+        "content" : page.content.html.clone(),
+        "page_name" : page.name.to_owned(),
+        "page" : page.front_matter.to_liquid(),
+        "terms" : taxonomy::page_terms(&page.front_matter),
+        "toc" : liquid::model::to_value(&page.content.toc).expect("Could not convert toc"),
+    })
+}
+
+fn load_markdown(entry: &Path, ctx: &BuildContext) -> Result<(FrontMatter, RenderedMarkdown)> {
+    let mut markdown = String::new();
+    File::open(entry)
+        .and_then(|mut f| f.read_to_string(&mut markdown))
+        .context("failed to open markdown file")?;
+    let (front_matter, body) =
+        front_matter::split(markdown.as_str()).context("invalid front matter")?;
+    let expanded = ctx.shortcodes.expand(body).context("invalid shortcode")?;
+    let links = PageLinks {
+        index: &ctx.link_index,
+        source: entry,
     };
-    let file_path = target_dir.join(html_file.as_path());
-    let result = File::create(file_path.as_path()).and_then(|mut f| f.write_all(html.as_bytes()));
-    assert!(
-        result.is_ok(),
-        "Could not write html file {}: {:?}",
-        file_path.as_path().display(),
-        result.err()
+    let mut content = markdown::convert_markdown_to_html(
+        &expanded.markdown,
+        ctx.conf
+            .markdown
+            .as_ref()
+            .expect("Invalid config: expected markdown settings are missing"),
+        Some(&links),
     );
+    content.html = expanded.insert_snippets(&content.html);
+    Ok((front_matter, content))
+}
+
+fn write_html_file(
+    ctx: &BuildContext,
+    html: &str,
+    target_dir: &Path,
+    entry: &DirEntry,
+) -> Result<()> {
+    let file_path = target_dir.join(markdown::html_file_name(entry.path(), ctx.main_pages));
+    File::create(file_path.as_path())
+        .and_then(|mut f| f.write_all(html.as_bytes()))
+        .with_context(|| format!("Could not write html file {}", file_path.display()))?;
     log::info!("Rendered html {}", file_path.display());
+    Ok(())
+}
+
+/// Writes the json export of a page next to its html file if `json_export`
+/// is switched on.
+fn write_page_json(ctx: &BuildContext, source: &Path, html: &str, target_dir: &Path) -> Result<()> {
+    if !ctx.conf.json_export.unwrap_or(false) {
+        return Ok(());
+    }
+    // the 404 page is no part of the site
+    let Some(site_page) = ctx.site_pages.iter().find(|page| page.source == source) else {
+        return Ok(());
+    };
+    let json = json::page(site_page, html, ctx.conf.base_url.as_deref());
+    let json_url = json::json_url(&site_page.url);
+    let file_name = Path::new(&json_url).file_name().unwrap_or_default();
+    write_json_file(&json, &target_dir.join(file_name))
+}
+
+fn write_json_file(json: &serde_json::Value, file_path: &Path) -> Result<()> {
+    let json = serde_json::to_string_pretty(json)?;
+    fs::write(file_path, json)
+        .with_context(|| format!("Could not write json file {}", file_path.display()))?;
+    log::info!("Rendered json {}", file_path.display());
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_project::TestProject;
+    use liquid::ValueView;
+
+    /// Builds a test project and returns the result with the output directory.
+    fn build_project(project: &TestProject, drafts: bool) -> (Result<()>, PathBuf) {
+        let conf = Config::load(project.path()).unwrap();
+        let result = build(project.path(), &conf, true, drafts);
+        (result, PathBuf::from(conf.output_dir.unwrap()))
+    }
+
+    #[test]
+    fn failing_page_does_not_stop_the_build() {
+        let project = TestProject::new("failing_page");
+        project
+            .write("config.toml", "title = \"Club\"\n")
+            .write("index.md", "# Home")
+            .write("about.md", "# About")
+            .write("broken.md", "+++\ntitle = \n+++\n# Broken")
+            .write("party.md", "+++\ndate = \"next week\"\n+++\n# Party");
+        let (result, output_dir) = build_project(&project, false);
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("2 of 4 page(s)"), "{err}");
+        assert!(output_dir.join("index.html").is_file());
+        assert!(output_dir.join("about.html").is_file());
+        assert!(!output_dir.join("broken.html").exists());
+    }
+
+    #[test]
+    fn section_without_index_lists_all_posts() {
+        let project = TestProject::new("section_without_index");
+        project
+            .write(
+                "config.toml",
+                "title = \"Club\"\n[[sections]]\ndir = \"Posts\"\n",
+            )
+            .write("index.md", "# Home")
+            .write("Posts/a.md", "# First party")
+            .write("Posts/b.md", "# Second party");
+        let (result, output_dir) = build_project(&project, false);
+        result.unwrap();
+        let read = |name: &str| fs::read_to_string(output_dir.join(name)).unwrap();
+        let listing = read("Posts/index.html");
+        assert!(listing.contains("Posts/a.html") && listing.contains("Posts/b.html"));
+        assert!(read("Posts/a.html").contains("First party"));
+        assert!(read("Posts/b.html").contains("Second party"));
+    }
+
+    #[test]
+    fn drafts_stay_out_of_the_sitemap() {
+        let project = TestProject::new("drafts_sitemap");
+        project
+            .write(
+                "config.toml",
+                "title = \"Club\"\nbase_url = \"https://example.com\"\n[sitemap]\n",
+            )
+            .write("index.md", "# Home")
+            .write("party.md", "+++\ndraft = true\n+++\n# Party")
+            .write("Plans/_draft", "")
+            .write("Plans/index.md", "# Plans");
+        let (result, output_dir) = build_project(&project, true);
+        result.unwrap();
+        assert!(output_dir.join("Plans/index.html").is_file());
+        let sitemap = fs::read_to_string(output_dir.join(sitemap::SITEMAP_XML)).unwrap();
+        assert!(sitemap.contains("<loc>https://example.com/index.html</loc>"));
+        assert!(!sitemap.contains("party") && !sitemap.contains("Plans"));
+    }
+
+    #[test]
+    fn json_export_writes_a_json_feed() {
+        let project = TestProject::new("json_feed");
+        project
+            .write(
+                "config.toml",
+                "title = \"Club\"\nbase_url = \"https://example.com\"\njson_export = true\n",
+            )
+            .write("index.md", "# Home")
+            .write("party.md", "+++\ndate = \"2024-03-01\"\n+++\n# Party");
+        let (result, output_dir) = build_project(&project, false);
+        result.unwrap();
+        let json_feed = fs::read_to_string(output_dir.join("feed.json")).unwrap();
+        assert!(json_feed.contains("\"url\": \"https://example.com/party.html\""));
+        assert!(!output_dir.join("atom.xml").exists());
+    }
+
     #[test]
     fn remove_numbered_prefix_default_config() {
         let conf = Config::default();
@@ -461,6 +1256,7 @@ mod tests {
         assert_eq!(remove_number_prefix("1_name", &conf), "name");
         assert_eq!(remove_number_prefix("12_name", &conf), "name");
         assert_eq!(remove_number_prefix("123_name", &conf), "name");
+        assert_eq!(remove_number_prefix("2024-03-01_name", &conf), "name");
     }
 
     #[test]
@@ -477,6 +1273,7 @@ mod tests {
         assert_eq!(remove_number_prefix("1_name", &conf), "name");
         assert_eq!(remove_number_prefix("12_name", &conf), "name");
         assert_eq!(remove_number_prefix("123_name", &conf), "name");
+        assert_eq!(remove_number_prefix("2024-03-01_name", &conf), "name");
     }
 
     #[test]
@@ -493,5 +1290,158 @@ mod tests {
         assert_eq!(remove_number_prefix("1_name", &conf), "1_name");
         assert_eq!(remove_number_prefix("12_name", &conf), "12_name");
         assert_eq!(remove_number_prefix("123_name", &conf), "123_name");
+        assert_eq!(
+            remove_number_prefix("2024-03-01_name", &conf),
+            "2024-03-01_name"
+        );
+    }
+
+    fn nav_node(name: &str, depth: usize, children: Vec<NavNode>) -> NavNode {
+        NavNode {
+            name: name.to_string(),
+            url: format!("{name}/index.html"),
+            depth,
+            children,
+        }
+    }
+
+    #[test]
+    fn flatten_nav_tree_to_menu_cmds() {
+        let nav_tree = vec![
+            nav_node("Galleries", 1, vec![]),
+            nav_node(
+                "Posts",
+                1,
+                vec![
+                    nav_node("a", 2, vec![nav_node("deep", 3, vec![])]),
+                    nav_node("b", 2, vec![]),
+                ],
+            ),
+            nav_node("Tutorials", 1, vec![nav_node("Training", 2, vec![])]),
+        ];
+        let mut nav_entries = Vec::new();
+        let mut prev_depth = 1;
+        flatten_nav_nodes(&nav_tree, &mut nav_entries, &mut prev_depth);
+        let menu_cmds: Vec<_> = nav_entries
+            .iter()
+            .map(|entry| {
+                let entry = entry.as_object().unwrap();
+                (
+                    entry.get("name").unwrap().to_kstr().to_string(),
+                    entry.get("menu_cmd").unwrap().to_kstr().to_string(),
+                    entry.get("level_depth").unwrap().to_kstr().to_string(),
+                )
+            })
+            .collect();
+        let expected = [
+            ("Galleries", "None", "0"),
+            ("Posts", "OpenLevel", "0"),
+            ("a", "OpenLevel", "0"),
+            ("deep", "None", "0"),
+            ("b", "CloseLevel", "0"),
+            ("Tutorials", "CloseOpenLevel", "0"),
+            ("Training", "None", "0"),
+        ];
+        assert_eq!(
+            menu_cmds,
+            expected.map(|(n, c, d)| (n.to_string(), c.to_string(), d.to_string()))
+        );
+        assert_eq!(prev_depth, 2);
+    }
+
+    #[test]
+    fn nav_tree_marks_active_page() {
+        let navigation = Navigation {
+            tree: vec![
+                nav_node("Galleries", 1, vec![]),
+                nav_node(
+                    "Posts",
+                    1,
+                    vec![nav_node("a", 2, vec![nav_node("deep", 3, vec![])])],
+                ),
+            ],
+            items: Vec::new(),
+            home: None,
+        };
+        let tree = navigation.tree_for("deep/index.html");
+        let flags = |node: &dyn ValueView| {
+            let node = node.as_object().unwrap();
+            (
+                node.get("is_active").unwrap().to_kstr().to_string(),
+                node.get("is_ancestor").unwrap().to_kstr().to_string(),
+            )
+        };
+        let child = |node: &dyn ValueView| {
+            let children = node.as_object().unwrap().get("children").unwrap();
+            children.as_array().unwrap().first().unwrap().to_value()
+        };
+        let tree = tree.as_array().unwrap();
+        let galleries = tree.first().unwrap();
+        let posts = tree.get(1).unwrap();
+        let a = child(posts);
+        let deep = child(&a);
+        assert_eq!(flags(galleries), ("false".into(), "false".into()));
+        assert_eq!(flags(posts), ("false".into(), "true".into()));
+        assert_eq!(flags(&a), ("false".into(), "true".into()));
+        assert_eq!(flags(&deep), ("true".into(), "false".into()));
+    }
+
+    #[test]
+    fn breadcrumbs_and_neighbours() {
+        let navigation = Navigation {
+            tree: vec![
+                nav_node("Galleries", 1, vec![]),
+                nav_node(
+                    "Posts",
+                    1,
+                    vec![nav_node("a", 2, vec![]), nav_node("b", 2, vec![])],
+                ),
+            ],
+            items: Vec::new(),
+            home: Some(nav_node("Home", 0, vec![])),
+        };
+        let names = |links: &liquid::model::Value| {
+            links
+                .as_array()
+                .unwrap()
+                .values()
+                .map(|link| {
+                    link.as_object()
+                        .unwrap()
+                        .get("name")
+                        .unwrap()
+                        .to_kstr()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&navigation.breadcrumbs("a/index.html")),
+            ["Home", "Posts", "a"]
+        );
+        let name = |link: &liquid::model::Value| {
+            link.as_object()
+                .map(|link| link.get("name").unwrap().to_kstr().to_string())
+        };
+        let (prev, next) = navigation.neighbours("a/index.html");
+        assert_eq!(name(&prev).as_deref(), Some("Posts"));
+        assert_eq!(name(&next).as_deref(), Some("b"));
+        let (prev, next) = navigation.neighbours("Home/index.html");
+        assert_eq!(name(&prev), None);
+        assert_eq!(name(&next).as_deref(), Some("Galleries"));
+    }
+
+    #[test]
+    fn not_found_page_links_start_at_base_url() {
+        let conf = |base_url: Option<&str>| Config {
+            base_url: base_url.map(str::to_string),
+            ..Default::default()
+        };
+        assert_eq!(not_found_root_dir(&conf(None)), "/");
+        assert_eq!(not_found_root_dir(&conf(Some("https://example.com"))), "/");
+        assert_eq!(
+            not_found_root_dir(&conf(Some("https://example.com/club/"))),
+            "/club/"
+        );
     }
 }
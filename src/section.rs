@@ -0,0 +1,163 @@
+use crate::config::{DEFAULT_PAGINATE_BY, Section};
+use crate::filter::find_index_md;
+use crate::site::{self, Site, SitePage};
+use liquid::model::Value;
+use std::path::{Path, PathBuf};
+
+/// Directory of the second and following listing pages of a section.
+pub static PAGE_DIR: &str = "page";
+
+/// One listing page of a posts section.
+pub struct Listing {
+    /// Path of the html file relative to the output directory.
+    pub url: String,
+    /// Number of directories between the output directory and the html file.
+    pub depth: usize,
+    pub paginator: Value,
+}
+
+impl Section {
+    pub fn dir(&self, path: &Path) -> PathBuf {
+        path.join(&self.dir)
+    }
+
+    /// The `index.md` whose front matter and content become part of the first
+    /// listing page. It is not rendered on its own.
+    pub fn index_markdown(&self, path: &Path) -> Option<PathBuf> {
+        find_index_md(&self.dir(path))
+    }
+
+    /// The pages beneath the section directory, newest first. Undated pages
+    /// come last.
+    pub fn posts<'a>(&self, path: &Path, site: &'a Site) -> Vec<&'a SitePage> {
+        let dir = self.dir(path);
+        let index = self.index_markdown(path);
+        let mut posts: Vec<_> = site
+            .pages
+            .iter()
+            .filter(|page| page.source.starts_with(&dir) && Some(&page.source) != index.as_ref())
+            .collect();
+        site::sort_newest_first(&mut posts);
+        posts
+    }
+
+    /// Splits the posts into listing pages of `paginate_by` posts. The first
+    /// page is the `index.html` of the section, the others are
+    /// `page/<n>/index.html`.
+    pub fn listings(&self, path: &Path, site: &Site) -> Vec<Listing> {
+        let posts = self.posts(path, site);
+        let per_page = self.paginate_by.unwrap_or(DEFAULT_PAGINATE_BY).max(1);
+        let total_pages = posts.len().div_ceil(per_page).max(1);
+        let base = self.dir.trim_matches('/');
+        let base_depth = Path::new(base).components().count();
+        let url = |page: usize| match page {
+            1 => format!("{base}/index.html"),
+            page => format!("{base}/{PAGE_DIR}/{page}/index.html"),
+        };
+        let chunks: Vec<_> = if posts.is_empty() {
+            vec![&[][..]]
+        } else {
+            posts.chunks(per_page).collect()
+        };
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(idx, chunk)| {
+                let page = idx + 1;
+                let optional_url = |page: usize| {
+                    (1..=total_pages)
+                        .contains(&page)
+                        .then(|| url(page))
+                        .map_or(Value::Nil, Value::scalar)
+                };
+                let paginator = liquid::object!({
+                    "pages": chunk.iter().map(|post| post.to_liquid()).collect::<Vec<_>>(),
+                    "page": page,
+                    "per_page": per_page,
+                    "total_pages": total_pages,
+                    "total_items": posts.len(),
+                    "first_url": url(1),
+                    "last_url": url(total_pages),
+                    "previous_url": optional_url(page - 1),
+                    "next_url": optional_url(page + 1),
+                });
+                Listing {
+                    url: url(page),
+                    depth: if page == 1 {
+                        base_depth
+                    } else {
+                        base_depth + 2
+                    },
+                    paginator: Value::Object(paginator),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liquid::ValueView;
+    use time::macros::datetime;
+
+    fn site_page(url: &str, date: Option<time::OffsetDateTime>) -> SitePage {
+        SitePage {
+            source: Path::new("blog").join(url).join("index.md"),
+            url: format!("{url}/index.html"),
+            title: url.to_string(),
+            section: Vec::new(),
+            depth: 1,
+            date,
+            updated: None,
+            front_matter: crate::front_matter::FrontMatter::default(),
+        }
+    }
+
+    #[test]
+    fn paginate_posts_by_date() {
+        let site = Site {
+            pages: vec![
+                site_page("Posts/a", Some(datetime!(2024-01-01 0:00 UTC))),
+                site_page("Posts/b", None),
+                site_page("Posts/c", Some(datetime!(2024-03-01 0:00 UTC))),
+                site_page("About", None),
+            ],
+        };
+        let section = Section {
+            dir: "Posts".to_string(),
+            paginate_by: Some(2),
+            layout: None,
+        };
+        let listings = section.listings(Path::new("blog"), &site);
+        let urls: Vec<_> = listings.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(urls, ["Posts/index.html", "Posts/page/2/index.html"]);
+        assert_eq!(listings[1].depth, 3);
+        let titles = |listing: &Listing| {
+            let paginator = listing.paginator.as_object().unwrap();
+            paginator
+                .get("pages")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .values()
+                .map(|page| {
+                    page.as_object()
+                        .unwrap()
+                        .get("title")
+                        .unwrap()
+                        .to_kstr()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles(&listings[0]), ["Posts/c", "Posts/a"]);
+        assert_eq!(titles(&listings[1]), ["Posts/b"]);
+        let paginator = listings[0].paginator.as_object().unwrap();
+        assert!(paginator.get("previous_url").unwrap().is_nil());
+        assert_eq!(
+            paginator.get("next_url").unwrap().to_kstr(),
+            "Posts/page/2/index.html"
+        );
+    }
+}
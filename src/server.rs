@@ -1,16 +1,21 @@
 use axum::Router;
 use std::net::SocketAddr;
-use tower_http::{services::ServeDir, trace::TraceLayer};
+use std::path::Path;
+use tower_http::services::{ServeDir, ServeFile};
+use tower_http::trace::TraceLayer;
 
 use crate::config::Config;
 
 fn create_route(conf: &Config) -> Router {
-    // serve generated content
-    Router::new().fallback_service(ServeDir::new(
+    let output_dir = Path::new(
         conf.output_dir
             .as_ref()
             .expect("Internal error [create_route(..)]: no output path!"),
-    ))
+    );
+    // serve generated content, missing files get the 404 page of the site
+    Router::new().fallback_service(
+        ServeDir::new(output_dir).not_found_service(ServeFile::new(output_dir.join("404.html"))),
+    )
 }
 
 #[tokio::main]
@@ -0,0 +1,322 @@
+use crate::config::Config;
+use anyhow::{Context, Result};
+use liquid::model::{KString, Value};
+use pulldown_cmark::{Event, Parser, Tag};
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+
+static SHORTCODE_DIR: &str = "shortcodes";
+
+static SHORTCODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{<\s*(/\*)?\s*(.*?)\s*(\*/)?\s*>\}\}").unwrap());
+static ARGUMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:([\w-]+)\s*=\s*)?(?:"([^"]*)"|'([^']*)'|([^\s"']+))"#).unwrap()
+});
+
+/// Liquid snippets from `template_dir/shortcodes` that can be called from
+/// markdown via `{{< name key="value" >}}`.
+pub struct Shortcodes {
+    templates: HashMap<String, liquid::Template>,
+    config: Value,
+    data: Value,
+}
+
+/// Markdown whose shortcodes are replaced by placeholders. The rendered
+/// shortcodes are put back into the html once the markdown was converted.
+pub struct ExpandedMarkdown {
+    pub markdown: String,
+    snippets: Vec<String>,
+}
+
+impl Shortcodes {
+    /// Loads the shortcodes of the `template_dir`. Besides their arguments
+    /// they see the `config` and the `data` of the project.
+    pub fn load(conf: &Config, data: Value, parser: &liquid::Parser) -> Result<Self> {
+        let mut templates = HashMap::new();
+        let config = conf.to_liquid();
+        let Some(template_dir) = conf.template_dir.as_ref() else {
+            return Ok(Self {
+                templates,
+                config,
+                data,
+            });
+        };
+        let shortcode_dir = Path::new(template_dir).join(SHORTCODE_DIR);
+        if !shortcode_dir.is_dir() {
+            return Ok(Self {
+                templates,
+                config,
+                data,
+            });
+        }
+        for entry in std::fs::read_dir(&shortcode_dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "liq") {
+                continue;
+            }
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .with_context(|| format!("invalid shortcode file name {}", path.display()))?
+                .to_owned();
+            let source = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read shortcode {}", path.display()))?;
+            let template = parser
+                .parse(&source)
+                .with_context(|| format!("failed to parse shortcode {}", path.display()))?;
+            templates.insert(name, template);
+        }
+        Ok(Self {
+            templates,
+            config,
+            data,
+        })
+    }
+
+    /// Renders all shortcodes of `markdown`. `{{</* name */>}}` is kept as the
+    /// literal text `{{< name >}}`. Shortcodes in code spans and code blocks
+    /// are left as they are, shortcodes in headings are an error.
+    pub fn expand(&self, markdown: &str) -> Result<ExpandedMarkdown> {
+        let spans = Spans::of(markdown);
+        let mut snippets = Vec::new();
+        let mut error = None;
+        let expanded = SHORTCODE.replace_all(markdown, |caps: &Captures| {
+            if caps.get(1).is_some() && caps.get(3).is_some() {
+                return format!("{{{{< {} >}}}}", &caps[2]);
+            }
+            let start = caps.get(0).map_or(0, |m| m.start());
+            if spans.is_code(start) {
+                return caps[0].to_string();
+            }
+            if spans.is_heading(start) {
+                error.get_or_insert_with(|| {
+                    anyhow::anyhow!("shortcode '{}' must not be used in a heading", &caps[2])
+                });
+                return String::new();
+            }
+            match self.render(&caps[2]) {
+                Ok(snippet) => {
+                    snippets.push(snippet);
+                    placeholder(snippets.len() - 1)
+                }
+                Err(err) => {
+                    error.get_or_insert(err);
+                    String::new()
+                }
+            }
+        });
+        if let Some(err) = error {
+            return Err(err);
+        }
+        Ok(ExpandedMarkdown {
+            markdown: expanded.into_owned(),
+            snippets,
+        })
+    }
+
+    fn render(&self, call: &str) -> Result<String> {
+        let (name, arguments) = call.split_once(char::is_whitespace).unwrap_or((call, ""));
+        let template = self
+            .templates
+            .get(name)
+            .with_context(|| format!("unknown shortcode '{name}'"))?;
+        let mut variables = parse_arguments(arguments);
+        variables
+            .entry("config")
+            .or_insert_with(|| self.config.clone());
+        variables.entry("data").or_insert_with(|| self.data.clone());
+        template
+            .render(&variables)
+            .with_context(|| format!("failed to render shortcode '{name}'"))
+    }
+}
+
+impl ExpandedMarkdown {
+    pub fn insert_snippets(&self, html: &str) -> String {
+        let mut html = html.to_owned();
+        for (idx, snippet) in self.snippets.iter().enumerate() {
+            let placeholder = placeholder(idx);
+            let paragraph = format!("<p>{placeholder}</p>\n");
+            html = if html.contains(&paragraph) {
+                html.replace(&paragraph, snippet)
+            } else {
+                html.replace(&placeholder, snippet)
+            };
+        }
+        html
+    }
+}
+
+/// The code and the headings of a markdown text by their byte ranges.
+struct Spans {
+    code: Vec<Range<usize>>,
+    headings: Vec<Range<usize>>,
+}
+
+impl Spans {
+    fn of(markdown: &str) -> Self {
+        let mut spans = Self {
+            code: Vec::new(),
+            headings: Vec::new(),
+        };
+        for (event, range) in Parser::new(markdown).into_offset_iter() {
+            match event {
+                Event::Code(_) | Event::Start(Tag::CodeBlock(_)) => spans.code.push(range),
+                Event::Start(Tag::Heading { .. }) => spans.headings.push(range),
+                _ => {}
+            }
+        }
+        spans
+    }
+
+    fn is_code(&self, offset: usize) -> bool {
+        self.code.iter().any(|range| range.contains(&offset))
+    }
+
+    fn is_heading(&self, offset: usize) -> bool {
+        self.headings.iter().any(|range| range.contains(&offset))
+    }
+}
+
+fn placeholder(idx: usize) -> String {
+    format!("NEPTUNGENSHORTCODE{idx}X")
+}
+
+/// Named arguments become variables of the same name, positional arguments
+/// are collected in `args`.
+fn parse_arguments(arguments: &str) -> liquid::model::Object {
+    let mut variables = liquid::model::Object::new();
+    let mut positional = Vec::new();
+    for caps in ARGUMENT.captures_iter(arguments) {
+        let value = caps
+            .get(2)
+            .or_else(|| caps.get(3))
+            .or_else(|| caps.get(4))
+            .map_or("", |m| m.as_str());
+        let value = Value::scalar(value.to_owned());
+        match caps.get(1) {
+            Some(key) => {
+                variables.insert(KString::from_ref(key.as_str()), value);
+            }
+            None => positional.push(value),
+        }
+    }
+    variables.insert("args".into(), Value::Array(positional));
+    variables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shortcodes() -> Shortcodes {
+        let parser = liquid::ParserBuilder::with_stdlib().build().unwrap();
+        let mut templates = HashMap::new();
+        templates.insert(
+            "youtube".to_string(),
+            parser
+                .parse(r#"<iframe src="https://www.youtube.com/embed/{{ id }}"></iframe>"#)
+                .unwrap(),
+        );
+        templates.insert(
+            "button".to_string(),
+            parser
+                .parse(r#"<a class="{{ class }}" href="{{ args[0] }}">{{ args[1] }}</a>"#)
+                .unwrap(),
+        );
+        templates.insert(
+            "contact".to_string(),
+            parser
+                .parse(r#"<a href="mailto:{{ config.extra.email }}">{{ config.title }}</a>"#)
+                .unwrap(),
+        );
+        templates.insert(
+            "board".to_string(),
+            parser
+                .parse(r"{% for member in data.board %}{{ member.name }} {% endfor %}")
+                .unwrap(),
+        );
+        let conf: Config =
+            toml::from_str("title = \"Dog club\"\n[extra]\nemail = \"info@example.com\"").unwrap();
+        let data = liquid::object!({
+            "board": [{ "name": "Anna" }, { "name": "Ben" }],
+        });
+        Shortcodes {
+            templates,
+            config: conf.to_liquid(),
+            data: Value::Object(data),
+        }
+    }
+
+    #[test]
+    fn expand_shortcodes() {
+        let markdown = "Intro\n\n{{< youtube id=\"abc\" >}}\n\nSee {{<button '/contact' \"Contact us\" class=big>}}!";
+        let expanded = shortcodes().expand(markdown).unwrap();
+        let html = expanded.insert_snippets(&format!(
+            "<p>Intro</p>\n<p>{}</p>\n<p>See {}!</p>\n",
+            placeholder(0),
+            placeholder(1)
+        ));
+        assert_eq!(
+            html,
+            "<p>Intro</p>\n<iframe src=\"https://www.youtube.com/embed/abc\"></iframe><p>See <a class=\"big\" href=\"/contact\">Contact us</a>!</p>\n"
+        );
+        assert_eq!(
+            expanded.markdown,
+            format!("Intro\n\n{}\n\nSee {}!", placeholder(0), placeholder(1))
+        );
+    }
+
+    #[test]
+    fn escaped_shortcodes_stay_literal() {
+        let expanded = shortcodes()
+            .expand("`{{</* youtube id=\"abc\" */>}}`")
+            .unwrap();
+        assert_eq!(expanded.markdown, "`{{< youtube id=\"abc\" >}}`");
+    }
+
+    #[test]
+    fn shortcodes_see_config() {
+        let expanded = shortcodes().expand("{{< contact >}}").unwrap();
+        assert_eq!(
+            expanded.insert_snippets(&placeholder(0)),
+            "<a href=\"mailto:info@example.com\">Dog club</a>"
+        );
+    }
+
+    #[test]
+    fn shortcodes_see_data() {
+        let expanded = shortcodes().expand("{{< board >}}").unwrap();
+        assert_eq!(expanded.insert_snippets(&placeholder(0)), "Anna Ben ");
+    }
+
+    #[test]
+    fn shortcodes_in_code_stay_literal() {
+        let markdown = "```html\n{{< youtube id=\"abc\" >}}\n```\n\nUse `{{< youtube id=\"abc\" >}}` for {{< youtube id=\"abc\" >}}";
+        let expanded = shortcodes().expand(markdown).unwrap();
+        assert_eq!(
+            expanded.markdown,
+            format!(
+                "```html\n{{{{< youtube id=\"abc\" >}}}}\n```\n\nUse `{{{{< youtube id=\"abc\" >}}}}` for {}",
+                placeholder(0)
+            )
+        );
+    }
+
+    #[test]
+    fn shortcodes_in_headings_are_rejected() {
+        let err = shortcodes()
+            .expand("# Video {{< youtube id=\"abc\" >}}\n\nText")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("heading"), "{err}");
+    }
+
+    #[test]
+    fn unknown_shortcode() {
+        assert!(shortcodes().expand("{{< vimeo 123 >}}").is_err());
+    }
+}
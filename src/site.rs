@@ -0,0 +1,295 @@
+use crate::config::Config;
+use crate::filter::{MainPages, is_markdown, is_not_found_markdown, is_not_hidden};
+use crate::front_matter::{self, FrontMatter};
+use crate::publish::{self, Unpublished};
+use crate::render::remove_number_prefix;
+use anyhow::Result;
+use liquid::model::Value;
+use regex::Regex;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+use walkdir::WalkDir;
+
+static DATE_PREFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d{4}-\d{2}-\d{2})_").unwrap());
+
+/// A published page of the site as seen by the templates of all other pages.
+#[derive(Debug)]
+pub struct SitePage {
+    pub source: PathBuf,
+    /// Path of the html file relative to the output directory.
+    pub url: String,
+    pub title: String,
+    /// Cleaned names of the directories from the site root to the page.
+    pub section: Vec<String>,
+    pub depth: usize,
+    pub date: Option<OffsetDateTime>,
+    pub updated: Option<OffsetDateTime>,
+    pub front_matter: FrontMatter,
+}
+
+/// All published pages of a build in alphabetical order of their paths. The
+/// 404 page is left out.
+#[derive(Debug, Default)]
+pub struct Site {
+    pub pages: Vec<SitePage>,
+}
+
+impl Site {
+    /// Reads the front matter of every published page of the project. Dates
+    /// without an offset are taken to be in `offset`. Pages that cannot be
+    /// read are left out and added to `errors`.
+    pub fn collect(
+        path: &Path,
+        conf: &Config,
+        main_pages: &MainPages,
+        unpublished: &Unpublished,
+        offset: UtcOffset,
+        errors: &mut Vec<anyhow::Error>,
+    ) -> Self {
+        let entries = WalkDir::new(path)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(is_not_hidden)
+            .filter_map(Result::ok)
+            .filter(|e| {
+                is_markdown(e)
+                    && !unpublished.contains(e.path())
+                    && !is_not_found_markdown(path, e.path())
+            });
+        let mut pages = Vec::new();
+        for entry in entries {
+            match site_page(path, entry.path(), conf, main_pages, offset) {
+                Ok(page) => pages.push(page),
+                Err(err) => {
+                    errors.push(err.context(format!("failed to build {}", entry.path().display())));
+                }
+            }
+        }
+        Self { pages }
+    }
+
+    /// A hash of what the templates see of the pages, apart from modification
+    /// times taken from the files. All pages have to be rendered again when it
+    /// changes.
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha1::new();
+        for page in &self.pages {
+            let date = page.date.and_then(|date| date.format(&Rfc3339).ok());
+            // liquid objects are unordered, the json conversion sorts the keys
+            let front_matter =
+                serde_json::to_value(page.front_matter.to_liquid()).unwrap_or_default();
+            let entry = serde_json::json!([page.url, page.title, page.section, date, front_matter]);
+            hasher.update(entry.to_string());
+            hasher.update(b"\n");
+        }
+        hasher
+            .finalize()
+            .iter()
+            .fold(String::with_capacity(40), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            })
+    }
+
+    /// The `site` object of the templates.
+    pub fn to_liquid(&self) -> Value {
+        let pages = self.pages.iter().map(SitePage::to_liquid).collect();
+        Value::Object(liquid::object!({ "pages": Value::Array(pages) }))
+    }
+}
+
+impl SitePage {
+    pub fn to_liquid(&self) -> Value {
+        let date = |date: Option<OffsetDateTime>| {
+            date.and_then(|date| date.format(&Rfc3339).ok())
+                .map_or(Value::Nil, Value::scalar)
+        };
+        Value::Object(liquid::object!({
+            "url": self.url.clone(),
+            "title": self.title.clone(),
+            "section": self.section.clone(),
+            "depth": self.depth,
+            "date": date(self.date),
+            "updated": date(self.updated),
+            "page": self.front_matter.to_liquid(),
+        }))
+    }
+}
+
+/// Sorts pages by date, newest first. Undated pages come last.
+pub fn sort_newest_first(pages: &mut [&SitePage]) {
+    pages.sort_by(|a, b| match (a.date, b.date) {
+        (Some(a), Some(b)) => b.cmp(&a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.url.cmp(&b.url),
+    });
+}
+
+/// The path of the html file of `page` relative to the output directory,
+/// e.g. `Posts/index.html`.
+pub fn page_url(path: &Path, page: &Path, main_pages: &MainPages) -> String {
+    publish::output_file(path, Path::new(""), page, main_pages)
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn site_page(
+    path: &Path,
+    source: &Path,
+    conf: &Config,
+    main_pages: &MainPages,
+    offset: UtcOffset,
+) -> Result<SitePage> {
+    let markdown = fs::read_to_string(source)?;
+    // invalid front matter is reported when the page gets rendered
+    let front_matter = front_matter::split(&markdown)
+        .map(|(front_matter, _)| front_matter)
+        .unwrap_or_default();
+    let url = page_url(path, source, main_pages);
+    let section: Vec<String> = source
+        .parent()
+        .map(|dir| {
+            dir.components()
+                .skip(path.components().count())
+                .map(|part| {
+                    remove_number_prefix(&part.as_os_str().to_string_lossy(), conf).to_owned()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let title = front_matter.get_str("title").unwrap_or_else(|| {
+        if main_pages.contains(source) {
+            section
+                .last()
+                .cloned()
+                .or_else(|| conf.title.clone())
+                .unwrap_or_else(|| "Home".to_string())
+        } else {
+            let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+            remove_number_prefix(&stem, conf).to_owned()
+        }
+    });
+    let date = match front_matter.date("date", offset)? {
+        Some(date) => Some(date),
+        None => front_matter
+            .date("publish_date", offset)?
+            .or_else(|| prefix_date(source, main_pages, offset)),
+    };
+    let updated = front_matter.date("updated", offset)?.or_else(|| {
+        fs::metadata(source)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(OffsetDateTime::from)
+    });
+    Ok(SitePage {
+        source: source.to_path_buf(),
+        depth: section.len(),
+        url,
+        title,
+        section,
+        date,
+        updated,
+        front_matter,
+    })
+}
+
+/// The date of a `YYYY-MM-DD_` prefix of the directory of an index page or
+/// the file name of any other page.
+fn prefix_date(page: &Path, main_pages: &MainPages, offset: UtcOffset) -> Option<OffsetDateTime> {
+    let name = if main_pages.contains(page) {
+        page.parent()?.file_name()?
+    } else {
+        page.file_stem()?
+    };
+    let captures = DATE_PREFIX.captures(name.to_str()?)?;
+    front_matter::parse_date(&captures[1], offset).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_site_pages() {
+        let path = Path::new("examples/simple_blog");
+        let now = OffsetDateTime::now_utc();
+        let unpublished = Unpublished::collect(path, false, now, &mut Vec::new());
+        let mut errors = Vec::new();
+        let conf = Config::default();
+        let site = Site::collect(
+            path,
+            &conf,
+            &MainPages::collect(path, &conf),
+            &unpublished,
+            UtcOffset::UTC,
+            &mut errors,
+        );
+        assert!(errors.is_empty());
+        assert!(
+            site.pages
+                .iter()
+                .all(|page| !page.url.contains("Summer party"))
+        );
+        let training = site
+            .pages
+            .iter()
+            .find(|page| page.url == "Tutorials/Training/index.html")
+            .unwrap();
+        assert_eq!(training.section, ["Tutorials", "Training"]);
+        assert_eq!(training.depth, 2);
+        assert!(training.updated.is_some());
+    }
+
+    #[test]
+    fn fingerprint_ignores_modification_times() {
+        let site = |front_matter: &str, updated| {
+            let markdown = format!("+++\n{front_matter}\n+++\n# Party");
+            let (front_matter, _) = front_matter::split(&markdown).unwrap();
+            Site {
+                pages: vec![SitePage {
+                    source: PathBuf::from("Posts/Party.md"),
+                    url: "Posts/Party.html".to_string(),
+                    title: "Party".to_string(),
+                    section: vec!["Posts".to_string()],
+                    depth: 1,
+                    date: None,
+                    updated,
+                    front_matter,
+                }],
+            }
+        };
+        let fingerprint = site("tags = [\"summer\"]\nplace = \"park\"", None).fingerprint();
+        assert_eq!(
+            site(
+                "tags = [\"summer\"]\nplace = \"park\"",
+                Some(OffsetDateTime::now_utc())
+            )
+            .fingerprint(),
+            fingerprint
+        );
+        assert_ne!(
+            site("tags = [\"winter\"]\nplace = \"park\"", None).fingerprint(),
+            fingerprint
+        );
+    }
+
+    #[test]
+    fn dates_from_prefix() {
+        let main_pages = MainPages::default();
+        let prefix_date = |page| prefix_date(Path::new(page), &main_pages, UtcOffset::UTC);
+        let date = prefix_date("Posts/2024-03-01_Party.md").unwrap();
+        assert_eq!(date.date().to_string(), "2024-03-01");
+        assert!(prefix_date("Posts/Party.md").is_none());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::config;
+use crate::feed::{self, escape};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+pub static SITEMAP_XML: &str = "sitemap.xml";
+pub static ROBOTS_TXT: &str = "robots.txt";
+
+/// The rendered pages of a build by their path relative to the output
+/// directory with the time of their last modification if known.
+#[derive(Debug, Default)]
+pub struct Sitemap {
+    pages: BTreeMap<String, Option<OffsetDateTime>>,
+}
+
+impl Sitemap {
+    /// Adds a page. The first `lastmod` given for a page wins.
+    pub fn add(&mut self, url: String, lastmod: Option<OffsetDateTime>) {
+        let entry = self.pages.entry(url).or_default();
+        if entry.is_none() {
+            *entry = lastmod;
+        }
+    }
+
+    pub fn to_xml(&self, base_url: &str) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+        for (url, lastmod) in &self.pages {
+            xml.push_str("  <url>\n");
+            let _ = writeln!(
+                xml,
+                "    <loc>{}</loc>",
+                escape(&feed::permalink(base_url, url))
+            );
+            if let Some(lastmod) = lastmod.and_then(|date| date.format(&Rfc3339).ok()) {
+                let _ = writeln!(xml, "    <lastmod>{lastmod}</lastmod>");
+            }
+            xml.push_str("  </url>\n");
+        }
+        xml.push_str("</urlset>\n");
+        xml
+    }
+}
+
+/// A `robots.txt` for all crawlers with the `disallow` paths and the url of
+/// the sitemap.
+pub fn robots_txt(conf: &config::Sitemap, base_url: &str) -> String {
+    let mut robots = String::from("User-agent: *\n");
+    let disallow = conf.disallow.as_deref().unwrap_or_default();
+    if disallow.is_empty() {
+        robots.push_str("Disallow:\n");
+    }
+    for path in disallow {
+        let _ = writeln!(robots, "Disallow: {path}");
+    }
+    let _ = writeln!(
+        robots,
+        "\nSitemap: {}",
+        feed::permalink(base_url, SITEMAP_XML)
+    );
+    robots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn sitemap_xml() {
+        let mut sitemap = Sitemap::default();
+        sitemap.add("tags/index.html".to_string(), None);
+        sitemap.add(
+            "Posts/Summer party/index.html".to_string(),
+            Some(datetime!(2024-03-01 12:00 UTC)),
+        );
+        sitemap.add("Posts/Summer party/index.html".to_string(), None);
+        let xml = sitemap.to_xml("https://example.com/");
+        assert!(xml.contains(
+            "<url>\n    <loc>https://example.com/Posts/Summer%20party/index.html</loc>\n    \
+             <lastmod>2024-03-01T12:00:00Z</lastmod>\n  </url>"
+        ));
+        assert!(
+            xml.contains("<url>\n    <loc>https://example.com/tags/index.html</loc>\n  </url>")
+        );
+    }
+
+    #[test]
+    fn robots() {
+        let conf = config::Sitemap {
+            robots_txt: None,
+            disallow: Some(vec!["/scripts/".to_string()]),
+        };
+        assert_eq!(
+            robots_txt(&conf, "https://example.com"),
+            "User-agent: *\nDisallow: /scripts/\n\nSitemap: https://example.com/sitemap.xml\n"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::front_matter::FrontMatter;
+use crate::markdown;
+use crate::site::{self, Site, SitePage};
+use liquid::model::Value;
+use std::collections::BTreeMap;
+
+/// Front matter lists whose terms get listing pages, e.g. `tags = ["news"]`.
+pub static TAXONOMIES: [&str; 2] = ["tags", "categories"];
+
+/// A term of a taxonomy together with the pages using it.
+pub struct Term<'a> {
+    pub name: String,
+    pub slug: String,
+    pub pages: Vec<&'a SitePage>,
+}
+
+/// All terms of one taxonomy in alphabetical order.
+pub struct Taxonomy<'a> {
+    pub name: &'static str,
+    pub terms: Vec<Term<'a>>,
+}
+
+impl<'a> Taxonomy<'a> {
+    /// Collects the terms of the taxonomy `name` used by the pages of the
+    /// site. Terms are told apart by their slug, the first spelling wins.
+    /// Terms without a slug are skipped with a warning.
+    pub fn collect(name: &'static str, site: &'a Site) -> Self {
+        let mut terms: BTreeMap<String, Term<'a>> = BTreeMap::new();
+        for page in &site.pages {
+            for term in page.front_matter.get_list(name) {
+                let Some(slug) = term_slug(&term) else {
+                    log::warn!(
+                        "Skipped the {name} term '{term}' of {}, it has no letters or digits",
+                        page.source.display()
+                    );
+                    continue;
+                };
+                terms
+                    .entry(slug.clone())
+                    .or_insert_with(|| Term {
+                        name: term,
+                        slug,
+                        pages: Vec::new(),
+                    })
+                    .pages
+                    .push(page);
+            }
+        }
+        let mut terms: Vec<_> = terms.into_values().collect();
+        for term in &mut terms {
+            site::sort_newest_first(&mut term.pages);
+        }
+        terms.sort_by_key(|term| term.name.to_lowercase());
+        Self { name, terms }
+    }
+
+    /// Path of the term index relative to the output directory.
+    pub fn url(&self) -> String {
+        format!("{}/index.html", self.name)
+    }
+
+    pub fn to_liquid(&self) -> Value {
+        let terms = self
+            .terms
+            .iter()
+            .map(|term| term.to_liquid(self.name))
+            .collect::<Vec<_>>();
+        Value::Object(liquid::object!({
+            "name": self.name,
+            "url": self.url(),
+            "terms": terms,
+        }))
+    }
+}
+
+impl Term<'_> {
+    pub fn to_liquid(&self, taxonomy: &str) -> Value {
+        let pages = self
+            .pages
+            .iter()
+            .map(|page| page.to_liquid())
+            .collect::<Vec<_>>();
+        Value::Object(liquid::object!({
+            "name": self.name.clone(),
+            "slug": self.slug.clone(),
+            "url": term_url(taxonomy, &self.slug),
+            "pages": pages,
+        }))
+    }
+}
+
+/// The slug of a term. Terms without any letter or digit have none.
+fn term_slug(term: &str) -> Option<String> {
+    term.chars()
+        .any(char::is_alphanumeric)
+        .then(|| markdown::slugify(term))
+}
+
+/// Path of the listing page of a term relative to the output directory.
+pub fn term_url(taxonomy: &str, slug: &str) -> String {
+    format!("{taxonomy}/{slug}/index.html")
+}
+
+/// The `terms` of a page, e.g. `terms.tags`, with links to their listings.
+pub fn page_terms(front_matter: &FrontMatter) -> Value {
+    let terms = TAXONOMIES
+        .iter()
+        .map(|taxonomy| {
+            let links = front_matter
+                .get_list(taxonomy)
+                .into_iter()
+                .filter_map(|term| {
+                    let slug = term_slug(&term)?;
+                    Some(Value::Object(liquid::object!({
+                        "url": term_url(taxonomy, &slug),
+                        "name": term,
+                        "slug": slug,
+                    })))
+                })
+                .collect();
+            ((*taxonomy).into(), Value::Array(links))
+        })
+        .collect();
+    Value::Object(terms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::front_matter;
+    use liquid::ValueView;
+    use time::macros::datetime;
+
+    fn site_page(url: &str, header: &str, date: time::OffsetDateTime) -> SitePage {
+        let (front_matter, _) = front_matter::split(header).unwrap();
+        SitePage {
+            source: url.into(),
+            url: url.to_string(),
+            title: url.to_string(),
+            section: Vec::new(),
+            depth: 0,
+            date: Some(date),
+            updated: None,
+            front_matter,
+        }
+    }
+
+    #[test]
+    fn collect_terms() {
+        let site = Site {
+            pages: vec![
+                site_page(
+                    "a.html",
+                    "+++\ntags = [\"Youth\", \"news\"]\n+++\n",
+                    datetime!(2024-01-01 0:00 UTC),
+                ),
+                site_page(
+                    "b.html",
+                    "+++\ntags = [\"youth\", \"!!!\"]\n+++\n",
+                    datetime!(2024-02-01 0:00 UTC),
+                ),
+                site_page("c.html", "", datetime!(2024-03-01 0:00 UTC)),
+            ],
+        };
+        let tags = Taxonomy::collect("tags", &site);
+        let terms: Vec<_> = tags
+            .terms
+            .iter()
+            .map(|term| {
+                let pages: Vec<_> = term.pages.iter().map(|page| page.url.as_str()).collect();
+                (term.name.as_str(), term.slug.as_str(), pages)
+            })
+            .collect();
+        assert_eq!(
+            terms,
+            [
+                ("news", "news", vec!["a.html"]),
+                ("Youth", "youth", vec!["b.html", "a.html"]),
+            ]
+        );
+        assert!(Taxonomy::collect("categories", &site).terms.is_empty());
+        let links = page_terms(&site.pages[1].front_matter);
+        let links = links.as_object().unwrap().get("tags").unwrap();
+        assert_eq!(links.as_array().unwrap().size(), 1);
+    }
+}
@@ -1,33 +1,47 @@
 use crate::config::Config;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::PathBuf;
-
-fn load_template(name: &str, conf: &Config) -> Option<String> {
-    conf.template_dir.as_ref().map(|template_dir| {
-        let mut template = String::new();
-        let mut path_buf = PathBuf::new();
-        path_buf.push(template_dir);
-        path_buf.push(name);
-        match File::open(path_buf.as_path()).and_then(|mut f| f.read_to_string(&mut template)) {
-            Ok(_) => template,
-            Err(error) => panic!(
-                "failed to open page template {}: {}",
-                path_buf.display(),
-                error
-            ),
-        }
-    })
+use crate::front_matter;
+use crate::liquid_filters;
+use anyhow::{Context, Result};
+use liquid::partials::{EagerCompiler, InMemorySource};
+use std::collections::HashMap;
+use std::path::Path;
+use walkdir::WalkDir;
+
+static INCLUDES_DIR: &str = "_includes";
+/// Names the layout for all pages of a directory and its sub directories.
+static LAYOUT_FILE: &str = "_layout";
+pub static PAGE: &str = "page";
+pub static GALLERY: &str = "gallery";
+/// Layout of the listing pages of posts sections.
+pub static SECTION: &str = "section";
+/// Layout of the index of all terms of a taxonomy, e.g. `tags/index.html`.
+pub static TAXONOMY: &str = "taxonomy";
+/// Layout of the listing of the pages of one taxonomy term.
+pub static TAXONOMY_TERM: &str = "taxonomy_term";
+/// Layout of the page shown for missing pages.
+pub static NOT_FOUND: &str = "404";
+const MAX_LAYOUT_DEPTH: usize = 16;
+
+/// Returns the layout named by the nearest `_layout` file in the directory of
+/// `page` or one of its parents within the project `path`.
+pub fn directory_layout(path: &Path, page: &Path) -> Option<String> {
+    page.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(path))
+        .find_map(|dir| std::fs::read_to_string(dir.join(LAYOUT_FILE)).ok())
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
 }
 
-pub fn load_page(conf: &Config) -> String {
-    load_template("page.liq", conf).unwrap_or_else(|| {
-        r#"
+/// The template for pages if no `template_dir` is configured.
+const fn builtin_page() -> &'static str {
+    r#"
 <!DOCTYPE html>
 <html>
 <head>
-<title>{{title}}</title>
+<title>{% if page.title %}{{ page.title }} - {% endif %}{{title}}</title>
 <meta charset="UTF-8">
+{% if page.description %}<meta name="description" content="{{ page.description | escape }}">{% endif %}
 <meta name="viewport" content="width=device-width, initial-scale=1">
 <link rel="stylesheet" href="https://www.w3schools.com/lib/w3.css">
 <link rel="stylesheet" href="https://www.w3schools.com/lib/w3-theme-black.css">
@@ -119,18 +133,77 @@ html,body,h1,h2,h3,h4,h5,h6 {font-family: "Roboto", sans-serif}
 
 </body>
 </html>
-    "#.to_owned()
-    })
+    "#
+}
+
+/// The listing of posts sections unless the `template_dir` has a
+/// `section.liq`. It is wrapped by the page template.
+const fn builtin_section() -> &'static str {
+    r#"+++
+layout = "page"
++++
+{{ content }}
+<ul class="w3-ul">
+{% for post in paginator.pages %}
+    <li>
+        <a href="{{ root_dir }}{{ post.url }}">{{ post.title }}</a>
+        {% if post.date %}<span class="w3-opacity">{{ post.date | date: "%Y-%m-%d" }}</span>{% endif %}
+    </li>
+{% endfor %}
+</ul>
+{% if paginator.total_pages > 1 %}
+<p>
+    {% if paginator.previous_url %}<a href="{{ root_dir }}{{ paginator.previous_url }}">&laquo;</a>{% endif %}
+    {{ paginator.page }} / {{ paginator.total_pages }}
+    {% if paginator.next_url %}<a href="{{ root_dir }}{{ paginator.next_url }}">&raquo;</a>{% endif %}
+</p>
+{% endif %}
+"#
+}
+
+/// The index of a taxonomy unless the `template_dir` has a `taxonomy.liq`.
+/// It is wrapped by the page template.
+const fn builtin_taxonomy() -> &'static str {
+    r#"+++
+layout = "page"
++++
+<h1>{{ taxonomy.name | capitalize }}</h1>
+<ul class="w3-ul">
+{% for term in taxonomy.terms %}
+    <li><a href="{{ root_dir }}{{ term.url }}">{{ term.name }}</a> ({{ term.pages.size }})</li>
+{% endfor %}
+</ul>
+"#
 }
 
-pub fn load_gallery(conf: &Config) -> String {
-    load_template("gallery.liq", conf).unwrap_or_else(|| {
-        r#"
+/// The listing of a taxonomy term unless the `template_dir` has a
+/// `taxonomy_term.liq`. It is wrapped by the page template.
+const fn builtin_taxonomy_term() -> &'static str {
+    r#"+++
+layout = "page"
++++
+<h1>{{ term.name }}</h1>
+<ul class="w3-ul">
+{% for post in term.pages %}
+    <li>
+        <a href="{{ root_dir }}{{ post.url }}">{{ post.title }}</a>
+        {% if post.date %}<span class="w3-opacity">{{ post.date | date: "%Y-%m-%d" }}</span>{% endif %}
+    </li>
+{% endfor %}
+</ul>
+<p><a href="{{ root_dir }}{{ taxonomy.url }}">{{ taxonomy.name | capitalize }}</a></p>
+"#
+}
+
+/// The template for galleries if no `template_dir` is configured.
+const fn builtin_gallery() -> &'static str {
+    r#"
 <!DOCTYPE html>
 <html>
 <head>
-<title>{{title}}</title>
+<title>{% if page.title %}{{ page.title }} - {% endif %}{{title}}</title>
 <meta charset="UTF-8">
+{% if page.description %}<meta name="description" content="{{ page.description | escape }}">{% endif %}
 <meta name="viewport" content="width=device-width, initial-scale=1">
 <link rel="stylesheet" href="https://www.w3schools.com/lib/w3.css">
 <link rel="stylesheet" href="https://www.w3schools.com/lib/w3-theme-black.css">
@@ -228,6 +301,194 @@ html,body,h1,h2,h3,h4,h5,h6 {font-family: "Roboto", sans-serif}
 
 </body>
 </html>
-    "#.to_owned()
+    "#
+}
+
+/// A parsed template and the base layout named in its front matter.
+struct Layout {
+    template: liquid::Template,
+    parent: Option<String>,
+}
+
+/// All templates of a build. They are parsed once and shared by the threads
+/// rendering the pages.
+pub struct Templates {
+    parser: liquid::Parser,
+    layouts: HashMap<String, Layout>,
+}
+
+impl Templates {
+    /// Parses every `<name>.liq` of `template_dir`. Without a `template_dir`
+    /// the built-in page and gallery templates are used. The built-in section
+    /// and taxonomy templates are used unless `template_dir` has its own.
+    pub fn load(path: &Path, conf: &Config) -> Result<Self> {
+        let parser = parser(path, conf)?;
+        let mut layouts = HashMap::new();
+        let Some(template_dir) = conf.template_dir.as_ref() else {
+            for (name, source) in [
+                (PAGE, builtin_page()),
+                (GALLERY, builtin_gallery()),
+                (SECTION, builtin_section()),
+                (TAXONOMY, builtin_taxonomy()),
+                (TAXONOMY_TERM, builtin_taxonomy_term()),
+            ] {
+                let layout = parse_layout(&parser, source)
+                    .with_context(|| format!("failed to parse built-in {name} template"))?;
+                layouts.insert(name.to_owned(), layout);
+            }
+            return Ok(Self { parser, layouts });
+        };
+        let entries = WalkDir::new(template_dir)
+            .min_depth(1)
+            .max_depth(1)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "liq"));
+        for entry in entries {
+            let Some(name) = entry.path().file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let layout = std::fs::read_to_string(entry.path())
+                .map_err(anyhow::Error::from)
+                .and_then(|source| parse_layout(&parser, &source))
+                .with_context(|| format!("failed to parse template {}", entry.path().display()))?;
+            layouts.insert(name.to_owned(), layout);
+        }
+        for (name, source) in [
+            (SECTION, builtin_section()),
+            (TAXONOMY, builtin_taxonomy()),
+            (TAXONOMY_TERM, builtin_taxonomy_term()),
+        ] {
+            if !layouts.contains_key(name) {
+                let layout = parse_layout(&parser, source)
+                    .with_context(|| format!("failed to parse built-in {name} template"))?;
+                layouts.insert(name.to_owned(), layout);
+            }
+        }
+        Ok(Self { parser, layouts })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.layouts.contains_key(name)
+    }
+
+    pub const fn parser(&self) -> &liquid::Parser {
+        &self.parser
+    }
+
+    /// Renders the template `name` with `context`. Each base layout named in
+    /// a front matter is rendered afterwards with the previous output as
+    /// `content`.
+    pub fn render(&self, name: &str, context: &mut liquid::model::Object) -> Result<String> {
+        let mut name = name;
+        let mut output = None;
+        for _ in 0..MAX_LAYOUT_DEPTH {
+            let layout = self
+                .layouts
+                .get(name)
+                .with_context(|| format!("template '{name}.liq' does not exist"))?;
+            if let Some(content) = output {
+                context.insert("content".into(), liquid::model::Value::scalar(content));
+            }
+            output = Some(
+                layout
+                    .template
+                    .render(context)
+                    .with_context(|| format!("failed to render template '{name}.liq'"))?,
+            );
+            match layout.parent.as_deref() {
+                Some(parent) => name = parent,
+                None => return Ok(output.unwrap_or_default()),
+            }
+        }
+        anyhow::bail!(
+            "layouts are nested more than {MAX_LAYOUT_DEPTH} levels deep, is there a cycle?"
+        )
+    }
+}
+
+fn parse_layout(parser: &liquid::Parser, source: &str) -> Result<Layout> {
+    let (front_matter, body) = front_matter::split(source)?;
+    Ok(Layout {
+        template: parser.parse(body)?,
+        parent: front_matter.get_str("layout"),
     })
 }
+
+/// Builds the liquid parser. `{% include %}` and `{% render %}` look up their
+/// partials by the path relative to `template_dir/_includes`, e.g.
+/// `{% include "nav.liq" %}`. The filters of [`liquid_filters`] are added to
+/// the standard library.
+fn parser(path: &Path, conf: &Config) -> Result<liquid::Parser> {
+    let mut partials = EagerCompiler::<InMemorySource>::empty();
+    if let Some(template_dir) = conf.template_dir.as_ref() {
+        let includes_dir = Path::new(template_dir).join(INCLUDES_DIR);
+        let entries = WalkDir::new(&includes_dir)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file());
+        for entry in entries {
+            let name = entry
+                .path()
+                .strip_prefix(&includes_dir)?
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let source = std::fs::read_to_string(entry.path())
+                .with_context(|| format!("failed to read include {}", entry.path().display()))?;
+            partials.add(name, source);
+        }
+    }
+    liquid_filters::register(liquid::ParserBuilder::with_stdlib(), path, conf)
+        .partials(partials)
+        .build()
+        .context("failed to parse the templates in _includes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn templates(sources: &[(&str, &str)]) -> Templates {
+        let parser = liquid::ParserBuilder::with_stdlib().build().unwrap();
+        let layouts = sources
+            .iter()
+            .map(|(name, source)| ((*name).to_owned(), parse_layout(&parser, source).unwrap()))
+            .collect();
+        Templates { parser, layouts }
+    }
+
+    #[test]
+    fn render_nested_layouts() {
+        let templates = templates(&[
+            (
+                "page",
+                "+++\nlayout = \"base\"\n+++\n<main>{{ content }}</main>",
+            ),
+            ("base", "---\nlayout: html\n---\n<body>{{ content }}</body>"),
+            ("html", "<title>{{ title }}</title>{{ content }}"),
+        ]);
+        let mut context = liquid::object!({ "title": "Dogs", "content": "<p>Woof</p>" });
+        assert_eq!(
+            templates.render("page", &mut context).unwrap(),
+            "<title>Dogs</title><body><main><p>Woof</p></main></body>"
+        );
+    }
+
+    #[test]
+    fn layout_errors() {
+        let templates = templates(&[
+            ("page", "+++\nlayout = \"missing\"\n+++\n{{ content }}"),
+            ("a", "+++\nlayout = \"b\"\n+++\n{{ content }}"),
+            ("b", "+++\nlayout = \"a\"\n+++\n{{ content }}"),
+        ]);
+        let mut context = liquid::object!({ "content": "" });
+        assert!(templates.render("page", &mut context).is_err());
+        assert!(templates.render("a", &mut context).is_err());
+        assert!(templates.render("gallery", &mut context).is_err());
+    }
+}
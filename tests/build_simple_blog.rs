@@ -26,18 +26,89 @@ fn test_build() {
     if cfg!(windows) {
         // on Windows, the path separator is a backslash
         assert!(output.contains("PUBLIC\\index.html"));
+        assert!(output.contains("PUBLIC\\404.html"));
+        assert!(output.contains("PUBLIC\\Posts\\index.html"));
+        assert!(output.contains("PUBLIC\\Posts\\page\\2\\index.html"));
+        assert!(output.contains("PUBLIC\\tags\\index.html"));
+        assert!(output.contains("PUBLIC\\tags\\lorem-ipsum\\index.html"));
+        assert!(output.contains("PUBLIC\\categories\\background\\index.html"));
         assert!(output.contains("PUBLIC\\Galleries\\index.html"));
         assert!(output.contains("PUBLIC\\Tutorials\\Training\\index.html"));
+        assert!(output.contains("PUBLIC\\Tutorials\\Training\\exercises.html"));
         assert!(output.contains("PUBLIC\\Tutorials\\Feeding\\index.html"));
         assert!(output.contains("PUBLIC\\Posts\\Where does it come from\\index.html"));
         assert!(output.contains("PUBLIC\\Posts\\What is Lorem Ipsum\\index.html"));
+        assert!(!output.contains("PUBLIC\\Posts\\Summer party\\index.html"));
+        assert!(!output.contains("PUBLIC\\Posts\\Spring meeting\\index.html"));
     } else {
         // on Unix-like systems, the path separator is a forward slash
         assert!(output.contains("PUBLIC/index.html"));
+        assert!(output.contains("PUBLIC/404.html"));
+        assert!(output.contains("PUBLIC/Posts/index.html"));
+        assert!(output.contains("PUBLIC/Posts/page/2/index.html"));
+        assert!(output.contains("PUBLIC/tags/index.html"));
+        assert!(output.contains("PUBLIC/tags/lorem-ipsum/index.html"));
+        assert!(output.contains("PUBLIC/categories/background/index.html"));
         assert!(output.contains("PUBLIC/Galleries/index.html"));
         assert!(output.contains("PUBLIC/Tutorials/Training/index.html"));
+        assert!(output.contains("PUBLIC/Tutorials/Training/exercises.html"));
         assert!(output.contains("PUBLIC/Tutorials/Feeding/index.html"));
         assert!(output.contains("PUBLIC/Posts/Where does it come from/index.html"));
         assert!(output.contains("PUBLIC/Posts/What is Lorem Ipsum/index.html"));
+        assert!(!output.contains("PUBLIC/Posts/Summer party/index.html"));
+        assert!(!output.contains("PUBLIC/Posts/Spring meeting/index.html"));
     }
+    let index = std::fs::read_to_string(examples_dir.join("PUBLIC").join("index.html")).unwrap();
+    assert!(!index.contains("404.html"));
+    let post = std::fs::read_to_string(
+        examples_dir
+            .join("PUBLIC")
+            .join("Posts")
+            .join("What is Lorem Ipsum")
+            .join("index.html"),
+    )
+    .unwrap();
+    assert!(post.contains("../../tags/typesetting/index.html"));
+    let atom = std::fs::read_to_string(examples_dir.join("PUBLIC").join("Posts").join("atom.xml"))
+        .unwrap();
+    assert!(atom.contains(
+        "<link href=\"https://www.example.com/Posts/What%20is%20Lorem%20Ipsum/index.html\"/>"
+    ));
+    assert!(
+        examples_dir
+            .join("PUBLIC")
+            .join("Posts")
+            .join("rss.xml")
+            .is_file()
+    );
+    let page_json = std::fs::read_to_string(
+        examples_dir
+            .join("PUBLIC")
+            .join("Posts")
+            .join("What is Lorem Ipsum")
+            .join("index.json"),
+    )
+    .unwrap();
+    assert!(page_json.contains("\"text\": \"What is Lorem Ipsum?\\nLorem Ipsum is simply"));
+    let site_json =
+        std::fs::read_to_string(examples_dir.join("PUBLIC").join("index.json")).unwrap();
+    assert!(site_json.contains("\"pages\": ["));
+    assert!(site_json.contains("\"json_url\": \"Posts/What is Lorem Ipsum/index.json\""));
+    assert!(site_json.contains("\"json_url\": \"_index.json\""));
+    let home_json =
+        std::fs::read_to_string(examples_dir.join("PUBLIC").join("_index.json")).unwrap();
+    assert!(home_json.contains("\"url\": \"index.html\""));
+    assert!(home_json.contains("\"text\": "));
+    let json_feed =
+        std::fs::read_to_string(examples_dir.join("PUBLIC").join("Posts").join("feed.json"))
+            .unwrap();
+    assert!(json_feed.contains("\"version\": \"https://jsonfeed.org/version/1.1\""));
+    let sitemap = std::fs::read_to_string(examples_dir.join("PUBLIC").join("sitemap.xml")).unwrap();
+    assert!(sitemap.contains("<loc>https://www.example.com/Posts/page/2/index.html</loc>"));
+    assert!(sitemap.contains("<loc>https://www.example.com/tags/index.html</loc>"));
+    assert!(!sitemap.contains("exercises.html"));
+    assert!(!sitemap.contains("404.html"));
+    assert!(!sitemap.contains("Summer%20party"));
+    let robots = std::fs::read_to_string(examples_dir.join("PUBLIC").join("robots.txt")).unwrap();
+    assert!(robots.contains("Sitemap: https://www.example.com/sitemap.xml"));
 }